@@ -1,14 +1,25 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::{LazyLock, PoisonError, RwLock},
+};
 
 use chrono::{TimeZone, Utc};
 use icalendar::{Calendar, Component, DatePerhapsTime, EventLike};
-use lol_html::{rewrite_str, RewriteStrSettings};
+use lol_html::{html_content::ContentType, rewrite_str, RewriteStrSettings};
 use mail_parser::{Addr, HeaderName, MessageParser, MimeHeaders};
 use rayon::prelude::*;
 use regex::Regex;
 use scraper::{Html, Selector};
 use url::Url;
 
+use crate::schemaorg::{
+    is_organization, parse_dining_reservation, parse_flight_reservation, parse_invoice,
+    parse_product, parse_sender_identity, parse_tickets, FlightReservation,
+    FoodEstablishmentReservation, Invoice, Organization, Product, Ticket,
+};
+
 #[derive(Debug, uniffi::Error)]
 pub enum ParserError {
     Base64DecodeFailed,
@@ -17,6 +28,8 @@ pub enum ParserError {
     EmailParseFailed,
     NoFromHeader,
     NoToHeader,
+    InvalidPattern,
+    LimitExceeded,
 }
 
 impl Display for ParserError {
@@ -28,20 +41,75 @@ impl Display for ParserError {
             ParserError::EmailParseFailed => write!(f, "Failed to parse email"),
             ParserError::NoFromHeader => write!(f, "Email doesn't have a from header"),
             ParserError::NoToHeader => write!(f, "Email doesn't have a to header"),
+            ParserError::InvalidPattern => write!(f, "Pattern is not a valid regex"),
+            ParserError::LimitExceeded => write!(f, "Email exceeds the parse limits"),
         }
     }
 }
 
 type Return<T> = Result<T, ParserError>;
 
-fn url_base64_decode(s: &str) -> Return<String> {
+#[derive(Debug, uniffi::Error)]
+pub enum AddressError {
+    EmptyInput,
+    ContainsWhitespace,
+    UnbalancedBrackets,
+    InvalidDisplayName,
+    MissingAt,
+    MissingLocalPart,
+    MissingDomain,
+    InvalidLocalPart,
+    InvalidDomain,
+    TooLong,
+}
+
+impl Display for AddressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressError::EmptyInput => write!(f, "Address is empty"),
+            AddressError::ContainsWhitespace => write!(f, "Address contains whitespace"),
+            AddressError::UnbalancedBrackets => write!(f, "Address has unbalanced angle brackets"),
+            AddressError::InvalidDisplayName => {
+                write!(f, "Display name has special characters but isn't quoted")
+            }
+            AddressError::MissingAt => write!(f, "Address doesn't have an @"),
+            AddressError::MissingLocalPart => write!(f, "Address doesn't have a local part"),
+            AddressError::MissingDomain => write!(f, "Address doesn't have a domain"),
+            AddressError::InvalidLocalPart => write!(f, "Local part has invalid characters"),
+            AddressError::InvalidDomain => write!(f, "Domain is not a valid host name"),
+            AddressError::TooLong => write!(f, "Address is too long"),
+        }
+    }
+}
+
+/// Base64 alphabet of a raw email.
+#[derive(uniffi::Enum, Debug, Clone, Copy, PartialEq)]
+enum Base64Variant {
+    /// `-` and `_` instead of `+` and `/`, as used by the Gmail API
+    UrlSafe,
+    /// `+` and `/`, optionally wrapped over several lines
+    Standard,
+    /// Picks `UrlSafe` or `Standard` from the characters in the input
+    Auto,
+}
+
+fn base64_decode(s: &str, variant: Base64Variant) -> Return<String> {
     use base64::{engine::general_purpose::STANDARD, Engine};
 
     if s.is_empty() {
         return Err(ParserError::EmptyInput);
     }
 
-    let normalized = s.replace('-', "+").replace('_', "/");
+    let variant = match variant {
+        Base64Variant::Auto if s.contains(['-', '_']) => Base64Variant::UrlSafe,
+        Base64Variant::Auto => Base64Variant::Standard,
+        variant => variant,
+    };
+
+    let normalized = match variant {
+        Base64Variant::Standard => s.chars().filter(|c| !c.is_ascii_whitespace()).collect(),
+        _ => s.replace('-', "+").replace('_', "/"),
+    };
     match STANDARD.decode(&normalized) {
         Ok(bytes) => match std::str::from_utf8(&bytes) {
             Ok(decoded) => Ok(decoded.to_string()),
@@ -70,8 +138,13 @@ impl DecodeHeader for String {
 
 #[derive(uniffi::Record)]
 struct Email {
+    /// The author, or with several authors the one matching the Sender header,
+    /// who actually sent the email (RFC 5322 section 3.6.2), falling back to the first
     from: EmailAddressWithText,
+    /// Every From author, in header order
     from_addresses: Vec<EmailAddress>,
+    /// Whether From lists several authors, so clients can show "A and B"
+    from_is_multiple: bool,
 
     to: EmailAddressWithText,
     to_addresses: Vec<EmailAddress>,
@@ -80,36 +153,234 @@ struct Email {
     bcc_addresses: Vec<EmailAddress>,
 
     subject: Option<String>,
+    subject_direction: Option<TextDirection>,
+    /// Subject without reply/forward prefixes (see `normalize_subject`)
+    subject_normalized: Option<String>,
 
-    /// Unix epoch in seconds
+    /// Unix epoch in seconds, taken from the most recent Received header when the Date
+    /// header is missing or invalid
     date: Option<i64>,
+    /// Unparsed value of the Date header
+    date_raw: Option<String>,
+    /// UTC offset of the Date header in seconds, e.g. 19800 for `+0530`, to show the
+    /// sender's local time. `None` for `-0000`, which means the zone is unknown.
+    date_offset_seconds: Option<i32>,
+    /// From the Importance header, falling back to X-Priority and Priority
+    priority: Option<MessagePriority>,
+    /// Signing domain (`d=`) of the first DKIM-Signature aligned with the From domain,
+    /// or of the first DKIM-Signature when none is. Signatures aren't verified.
+    dkim_domain: Option<String>,
+    /// Signing domains of every DKIM-Signature, in header order
+    dkim_domains: Vec<String>,
+    /// Whether any DKIM signing domain shares the From domain's organizational domain
+    /// (relaxed DMARC alignment). `None` when the email isn't signed.
+    dkim_aligned: Option<bool>,
+    /// SVG logo URL from the `l=` tag of BIMI-Location. It's added by the receiving
+    /// server once the sender was authenticated, and is never fetched here.
+    bimi_logo_url: Option<String>,
+    /// Whether the email is an automatic reply such as an out-of-office message
+    /// (see `is_auto_reply`)
+    is_auto_reply: bool,
+    sender_signals: SenderSignals,
     content_id: Option<String>,
     message_id: Option<String>,
     thread_name: Option<String>,
+    /// First language tag of the top-level Content-Language header, like `en-US`.
+    /// Declared by the sender, so it's more reliable than detecting the language.
+    content_language: Option<String>,
     mime_version: Option<String>,
 
     headers: Vec<Header>,
 
     text_bodies: Vec<EmailText>,
     html_bodies: Vec<EmailText>,
+    /// One line for list rows (see `email_preview`), so messages without a text body
+    /// still show something
+    preview: Option<String>,
+    /// Image alt texts of the first HTML body (see `extract_alt_text`), for emails that
+    /// are mostly images with little text to read out otherwise. Only set with
+    /// `ParseOptions::alt_text_summary`.
+    alt_text_summary: Option<String>,
 
     markups: Vec<String>,
     calendar_events: Vec<CalendarEvent>,
     microdata_items: Vec<MicrodataItem>,
+    images: Vec<EmailImage>,
 
     unsubscribe: Unsubscribe,
+    mailing_list: Option<MailingList>,
+
+    attachments: Vec<Attachment>,
+    /// Whether the email has any attachments counted in `attachment_count`
+    has_attachments: bool,
+    /// Number of attachments, excluding inline images referenced from the HTML body
+    /// and calendar invites
+    attachment_count: u32,
+
+    /// Received headers in header order, so the most recent hop comes first
+    received_chain: Vec<ReceivedHop>,
+    /// Whether the email is S/MIME or PGP signed or encrypted (see `message_security`).
+    /// Encrypted payloads are kept as opaque attachments.
+    security: Option<MessageSecurity>,
+
+    /// Parts that were partially skipped, only collected with
+    /// `ParseOptions::parse_diagnostics`
+    warnings: Vec<ParseWarning>,
 }
 
-#[derive(uniffi::Record)]
+/// Something in a body part that couldn't be parsed and was skipped
+#[derive(uniffi::Record, Debug, PartialEq)]
+struct ParseWarning {
+    /// Index of the MIME part in the email
+    part: u32,
+    message: String,
+}
+
+#[derive(uniffi::Record, Clone)]
 struct EmailText {
+    /// The body as sent, for HTML bodies its raw markup
     text: String,
     visible: Option<String>,
+    /// For HTML bodies, `text` with HTML entities decoded, and with its tags stripped
+    /// when `ParseOptions::decoded_text_strip_tags` is set. Search should index this
+    /// for HTML bodies and `text` for plain text ones, whose entities are already
+    /// decoded. `None` for plain text bodies.
+    #[uniffi(default = None)]
+    decoded_text: Option<String>,
+    /// MIME type of the part, like `text/html`; plain text bodies without a
+    /// Content-Type are `text/plain`
+    #[uniffi(default = "")]
+    content_type: String,
+    /// IMAP-style dotted path of the part in the MIME tree, like `1.2` for the second
+    /// part of the first part. Empty for text that isn't a MIME part, like snippets.
+    #[uniffi(default = "")]
+    part_id: String,
+    /// First language tag of the part's Content-Language header, like `en-US`
+    #[uniffi(default = None)]
+    content_language: Option<String>,
+    /// Whether `text`, `visible` or `decoded_text` were cut short by
+    /// `ParseOptions::preview_chars`
+    #[uniffi(default = false)]
+    truncated: bool,
+    /// Whether the part didn't declare a charset that could be used, so it was
+    /// decoded as UTF-8. `false` when the declared charset was trusted.
+    #[uniffi(default = false)]
+    charset_detected: bool,
+    /// When `charset_detected`, the share of characters that aren't replacement
+    /// characters or typical mojibake, from 0 to 1. Low values mean the text was
+    /// likely decoded with the wrong charset.
+    #[uniffi(default = None)]
+    charset_confidence: Option<f32>,
+}
+
+/// Options for `parse_email_with_options`. The defaults match `parse_email`.
+#[derive(uniffi::Record, Default)]
+struct ParseOptions {
+    /// Keep only the first N characters of each body's `text`, `visible` and `decoded_text`.
+    /// Characters are counted as grapheme clusters, so no character is ever split.
+    #[uniffi(default = None)]
+    preview_chars: Option<u32>,
+    /// Base64 alphabet of `raw`, `UrlSafe` when not set
+    #[uniffi(default = None)]
+    base64_variant: Option<Base64Variant>,
+    /// Unicode normalization applied to `subject` and `subject_normalized`, so the same
+    /// subject sent in different forms compares equal. Bodies are never normalized, as
+    /// that could change how they're displayed; use `normalize_unicode` on them explicitly.
+    #[uniffi(default = None)]
+    subject_normalization: Option<NormalizationForm>,
+    /// Fail with `ParserError::LimitExceeded` instead of parsing emails larger than these
+    #[uniffi(default = None)]
+    limits: Option<ParseLimits>,
+    /// Collect `Email::warnings` for JSON-LD scripts and calendar parts that were skipped
+    #[uniffi(default = false)]
+    parse_diagnostics: bool,
+    /// Convert HTML bodies to plain text for `EmailText::decoded_text`, instead of
+    /// only decoding their entities
+    #[uniffi(default = false)]
+    decoded_text_strip_tags: bool,
+    /// Keep only the JSON-LD markups whose `@context` is schema.org, dropping other
+    /// vocabularies
+    #[uniffi(default = false)]
+    schema_org_only: bool,
+    /// Fill `Attachment::content_base64` for attachments smaller than this many bytes
+    #[uniffi(default = None)]
+    inline_attachments_below_bytes: Option<u64>,
+    /// Fill `Attachment::data` with the decoded contents of inline parts, as needed by
+    /// `extract_brand_image`
+    #[uniffi(default = false)]
+    inline_part_data: bool,
+    /// Fill `Email::alt_text_summary`, which parses the first HTML body twice more
+    #[uniffi(default = false)]
+    alt_text_summary: bool,
+    /// Replace each body's `text` with its `visible` part, dropping the quoted history
+    /// of replies. Bodies without a detected quote are kept whole.
+    #[uniffi(default = false)]
+    visible_only: bool,
+}
+
+/// Bounds on the work `parse_email_with_options` does on a single email. Unset
+/// limits aren't enforced.
+#[derive(uniffi::Record, Default)]
+struct ParseLimits {
+    /// Size of the decoded email, estimated from the base64 input before decoding it
+    #[uniffi(default = None)]
+    max_bytes: Option<u64>,
+    /// MIME parts. Checked on the raw email from its boundary lines before the MIME
+    /// tree is built, then again on the parsed tree, which also counts the parts of
+    /// attached messages
+    #[uniffi(default = None)]
+    max_parts: Option<u32>,
+    /// Tags across all HTML bodies, checked before any HTML is parsed
+    #[uniffi(default = None)]
+    max_html_nodes: Option<u32>,
+}
+
+/// Estimates the MIME parts of a raw email without parsing it, as the root part
+/// plus one for every delimiter line of a `boundary` declared in the email.
+fn count_mime_parts(raw: &[u8]) -> usize {
+    static BOUNDARY_RE: LazyLock<regex::bytes::Regex> = LazyLock::new(|| {
+        regex::bytes::Regex::new(r#"(?i)\bboundary\s*=\s*(?:"([^"\r\n]+)"|([^\s;"]+))"#)
+            .expect("expression is valid")
+    });
+
+    let boundaries: HashSet<&[u8]> = BOUNDARY_RE
+        .captures_iter(raw)
+        .filter_map(|captures| captures.get(1).or(captures.get(2)))
+        .map(|boundary| boundary.as_bytes())
+        .collect();
+    if boundaries.is_empty() {
+        return 1;
+    }
+
+    let delimiters = raw
+        .split(|&b| b == b'\n')
+        .filter_map(|line| line.strip_prefix(b"--"))
+        .map(|line| line.trim_ascii_end())
+        .filter(|boundary| boundaries.contains(boundary))
+        .count();
+    1 + delimiters
+}
+
+impl ParseLimits {
+    fn check(limit: Option<impl Into<u64>>, value: usize) -> Return<()> {
+        match limit.map(Into::into) {
+            Some(limit) if value as u64 > limit => Err(ParserError::LimitExceeded),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(uniffi::Record)]
 struct EmailAddress {
     name: Option<String>,
     address: String,
+    /// Whether this entry stands for an RFC 5322 group without members, such as
+    /// `Undisclosed recipients:;`. Its `name` is the group name and `address` is empty.
+    #[uniffi(default = false)]
+    is_group: bool,
+    /// Name of the group this address was listed in
+    #[uniffi(default = None)]
+    group: Option<String>,
 }
 
 #[derive(uniffi::Record)]
@@ -138,9 +409,38 @@ fn parse_addr(value: &Addr<'_>) -> Option<EmailAddress> {
     Some(EmailAddress {
         name: value.name().map(ToOwned::to_owned),
         address: value.address().map(ToOwned::to_owned)?,
+        is_group: false,
+        group: None,
     })
 }
 
+/// Reads an address header, keeping the groups it lists (see `EmailAddress::is_group`).
+fn parse_address(address: &mail_parser::Address<'_>) -> Vec<EmailAddress> {
+    match address {
+        mail_parser::Address::List(addrs) => parse_addrs(addrs),
+        mail_parser::Address::Group(groups) => groups
+            .iter()
+            .flat_map(|group| {
+                let name = group.name.as_deref().map(str::trim).map(ToOwned::to_owned);
+                let mut members = parse_addrs(&group.addresses);
+                match name {
+                    Some(name) if members.is_empty() => members.push(EmailAddress {
+                        name: Some(name),
+                        address: String::new(),
+                        is_group: true,
+                        group: None,
+                    }),
+                    Some(name) => members
+                        .iter_mut()
+                        .for_each(|member| member.group = Some(name.to_owned())),
+                    None => {}
+                }
+                members
+            })
+            .collect(),
+    }
+}
+
 fn parse_addrs(addrs: &[Addr<'_>]) -> Vec<EmailAddress> {
     addrs
         .par_iter()
@@ -148,699 +448,6661 @@ fn parse_addrs(addrs: &[Addr<'_>]) -> Vec<EmailAddress> {
         .collect::<Vec<EmailAddress>>()
 }
 
-fn parse_text(body: String) -> EmailText {
-    let escaped = html_escape::decode_html_entities(&body);
-    EmailText {
-        visible: parse_visible_text(&escaped),
-        text: escaped.into_owned(),
+/// Normalizes an address so the different spellings of one mailbox are equal: it's
+/// lowercased, its `+tag` is removed, and for Gmail the dots of the local part are
+/// removed and `googlemail.com` becomes `gmail.com`. Angle brackets around it are
+/// ignored.
+#[uniffi::export]
+fn canonical_address(address: &str) -> String {
+    let address = address
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim()
+        .to_lowercase();
+    let Some((local, domain)) = address.rsplit_once('@') else {
+        return address;
+    };
+
+    let local = local.split('+').next().unwrap_or_default();
+    match domain {
+        "gmail.com" | "googlemail.com" => format!("{}@gmail.com", local.replace('.', "")),
+        _ => format!("{local}@{domain}"),
     }
 }
 
+/// Whether two addresses are the same mailbox once passed through `canonical_address`,
+/// e.g. `John.Doe+x@GMAIL.com` and `johndoe@gmail.com`. Use this for "from me"
+/// checks and deduplicating contacts.
 #[uniffi::export]
-fn parse_visible_text(body: &str) -> Option<String> {
-    let reply_sep_re =
-        Regex::new(r"On\s\w{3},\s(?:\d{1,2}|\w{3})\s(?:\d{1,2}|\w{3}),?\s\d{4}\sat\s\d{1,2}:\d{2}")
-            .expect("expression is valid");
+fn addresses_equal(a: &str, b: &str) -> bool {
+    canonical_address(a) == canonical_address(b)
+}
 
-    if !reply_sep_re.is_match(body) {
-        return None;
+/// Parses a single address typed by a user, such as `jane@example.com` or
+/// `"Doe, Jane" <jane@example.com>`, rejecting anything RFC 5322 doesn't allow.
+///
+/// Unlike the lenient parsing of received emails, obsolete syntax and comments are
+/// rejected, and the domain has to be a host name with at least two labels or an
+/// address literal like `[192.0.2.1]`.
+#[uniffi::export]
+fn parse_address_strict(input: &str) -> Result<EmailAddress, AddressError> {
+    // RFC 5321 path limits
+    const MAX_LOCAL_PART: usize = 64;
+    const MAX_ADDRESS: usize = 254;
+
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(AddressError::EmptyInput);
     }
 
-    let mut parts = reply_sep_re.splitn(body, 2);
-    parts.next().map(str::trim).map(ToOwned::to_owned)
-}
+    // The angle address starts at the last `<` outside the display name's quotes
+    let mut angle_start = None;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => angle_start = Some(i),
+            _ => {}
+        }
+    }
 
-fn parse_html(body: String) -> EmailText {
-    EmailText {
-        visible: parse_visible_html(&body),
-        text: body,
+    let (name, address) = match angle_start.map(|i| (&input[..i], &input[i + 1..])) {
+        Some((name, rest)) => {
+            let address = rest
+                .strip_suffix('>')
+                .filter(|address| !address.contains(['<', '>']))
+                .ok_or(AddressError::UnbalancedBrackets)?;
+            (parse_strict_display_name(name.trim())?, address)
+        }
+        None if input.contains('>') => return Err(AddressError::UnbalancedBrackets),
+        None => (None, input),
+    };
+
+    if address.chars().any(char::is_whitespace) {
+        return Err(AddressError::ContainsWhitespace);
+    }
+    let (local, domain) = address.rsplit_once('@').ok_or(AddressError::MissingAt)?;
+    if local.is_empty() {
+        return Err(AddressError::MissingLocalPart);
+    }
+    if domain.is_empty() {
+        return Err(AddressError::MissingDomain);
+    }
+    if local.len() > MAX_LOCAL_PART || address.len() > MAX_ADDRESS {
+        return Err(AddressError::TooLong);
     }
-}
 
-#[uniffi::export]
-fn parse_visible_html(body: &str) -> Option<String> {
-    if !body.contains("gmail_quote_container") {
-        return None;
+    let is_atext = |c: char| c.is_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c);
+    let is_dot_atom = |text: &str| {
+        text.split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+    };
+    let is_quoted = |text: &str| {
+        text.len() >= 2
+            && text.starts_with('"')
+            && text.ends_with('"')
+            && !text[1..text.len() - 1]
+                .replace("\\\\", "")
+                .replace("\\\"", "")
+                .contains('"')
+    };
+    if !is_dot_atom(local) && !is_quoted(local) {
+        return Err(AddressError::InvalidLocalPart);
     }
 
-    rewrite_str(
-        body,
-        RewriteStrSettings {
-            element_content_handlers: vec![
-                lol_html::element!(".gmail_quote", |el| {
-                    el.remove();
-                    Ok(())
-                }),
-                lol_html::element!(".gmail_quote_container", |el| {
-                    el.remove();
-                    Ok(())
-                }),
-            ],
-            ..RewriteStrSettings::new()
-        },
-    )
-    .ok()
+    let is_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+    };
+    let is_host = domain.split('.').count() >= 2 && domain.split('.').all(is_label);
+    let is_literal = domain.starts_with('[')
+        && domain.ends_with(']')
+        && domain[1..domain.len() - 1]
+            .parse::<std::net::IpAddr>()
+            .is_ok();
+    if !is_host && !is_literal {
+        return Err(AddressError::InvalidDomain);
+    }
+
+    Ok(EmailAddress {
+        name,
+        address: address.to_owned(),
+        is_group: false,
+        group: None,
+    })
 }
 
-#[uniffi::export]
-fn clean_html(body: String) -> Option<String> {
-    rewrite_str(
-        &body,
-        RewriteStrSettings {
-            element_content_handlers: vec![
-                lol_html::element!(
-                    ".gmail_quote, .gmail_quote_container, head, meta, title, link, script, style, img",
-                    |el| {
-                        el.remove();
-                        Ok(())
-                    }
-                ),
-                lol_html::element!("*[style]", |el| {
-                    if let Some(style) = el.get_attribute("style") {
-                        if style.contains("display:none") || style.contains("visibility:hidden") {
-                            el.remove();
-                        }
-                    }
+/// Reads the display name in front of an angle-bracketed address, unquoting it.
+fn parse_strict_display_name(name: &str) -> Result<Option<String>, AddressError> {
+    if name.is_empty() {
+        return Ok(None);
+    }
 
-                    el.remove_attribute("style");
-                    Ok(())
-                }),
-                lol_html::element!("*[class]", |el| {
-                    el.remove_attribute("class");
-                    Ok(())
-                }),
-                lol_html::element!("html, body, table, tbody, tr, td, div", |el| {
-                    el.remove_and_keep_content();
-                    Ok(())
-                }),
-                lol_html::comments!("*", |c| {
-                    c.remove();
-                    Ok(())
-                }),
-            ],
-            document_content_handlers: vec![lol_html::doc_comments!(|c| {
-                c.remove();
-                Ok(())
-            })],
+    if let Some(quoted) = name.strip_prefix('"') {
+        let quoted = quoted
+            .strip_suffix('"')
+            .ok_or(AddressError::InvalidDisplayName)?;
+        let mut unquoted = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => unquoted.push(chars.next().ok_or(AddressError::InvalidDisplayName)?),
+                '"' => return Err(AddressError::InvalidDisplayName),
+                c => unquoted.push(c),
+            }
+        }
+        return Ok(Some(unquoted));
+    }
 
-            ..RewriteStrSettings::new()
-        },
-    )
-    .ok()
-    .map(|x| x.trim().replace("\r\n", "").replace("\n", ""))
+    // An unquoted name is a phrase, which can't contain specials such as `,` or `@`
+    let is_phrase_char =
+        |c: char| c.is_alphanumeric() || c.is_whitespace() || "!#$%&'*+-/=?^_`{|}~.".contains(c);
+    if !name.chars().all(is_phrase_char) {
+        return Err(AddressError::InvalidDisplayName);
+    }
+    Ok(Some(name.to_owned()))
 }
 
+/// Formats an address as a header value, e.g. `"Doe, John" <john@example.com>`.
+///
+/// Names with special characters are quoted, and non-ASCII names are RFC 2047
+/// encoded. Groups without members are formatted as `Name:;`.
 #[uniffi::export]
-fn parse_email(raw: String) -> Return<Email> {
-    let raw = url_base64_decode(&raw)?;
-    let parser = MessageParser::default();
-    let message = parser.parse(&raw).ok_or(ParserError::EmailParseFailed)?;
+fn format_address(addr: &EmailAddress) -> String {
+    let name = addr
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
 
-    let from_header = message
-        .header_raw(HeaderName::From)
-        .ok_or(ParserError::NoFromHeader)?
-        .trim()
-        .to_owned()
-        .decode_header();
+    match name {
+        Some(name) if addr.is_group => format!("{}:;", format_display_name(name)),
+        Some(name) => format!("{} <{}>", format_display_name(name), addr.address),
+        None => addr.address.to_owned(),
+    }
+}
 
-    let from_addresses: Vec<EmailAddress> = message
-        .from()
-        .and_then(|addr| addr.as_list())
-        .map(parse_addrs)
-        .ok_or(ParserError::NoFromHeader)?;
+/// Formats a list of addresses as a header value, listing members of the same
+/// group together as `Group: a@example.com, b@example.com;`.
+#[uniffi::export]
+fn format_address_list(addrs: Vec<EmailAddress>) -> String {
+    let mut formatted: Vec<String> = Vec::new();
+    let mut addrs = addrs.iter().peekable();
 
-    let from = from_addresses.first().ok_or(ParserError::NoFromHeader)?;
-    let from = EmailAddressWithText {
-        name: from.name.to_owned(),
-        text: from_header,
-        address: from.address.to_owned(),
-    };
+    while let Some(addr) = addrs.next() {
+        let Some(group) = addr.group.as_deref().filter(|_| !addr.is_group) else {
+            formatted.push(format_address(addr));
+            continue;
+        };
 
-    let to_header = message
-        .header_raw(HeaderName::To)
-        .ok_or(ParserError::NoToHeader)?
-        .trim()
-        .to_owned()
-        .decode_header();
+        let mut members = vec![format_address(addr)];
+        while let Some(member) = addrs.next_if(|next| next.group.as_deref() == Some(group)) {
+            members.push(format_address(member));
+        }
+        formatted.push(format!(
+            "{}: {};",
+            format_display_name(group),
+            members.join(", ")
+        ));
+    }
 
-    let to_addresses: Vec<EmailAddress> = message
-        .to()
-        .and_then(|addr| addr.as_list())
-        .map(parse_addrs)
-        .ok_or(ParserError::NoToHeader)?;
+    formatted.join(", ")
+}
 
-    let to = to_addresses.first().ok_or(ParserError::NoToHeader)?;
-    let to = EmailAddressWithText {
-        name: to.name.to_owned(),
-        text: to_header,
-        address: to.address.to_owned(),
-    };
+/// Quotes a display name when it isn't a plain RFC 5322 phrase, or encodes it as
+/// RFC 2047 encoded words when it isn't ASCII.
+fn format_display_name(name: &str) -> String {
+    use base64::{engine::general_purpose::STANDARD, Engine};
 
-    let cc_addresses: Vec<EmailAddress> = message
-        .cc()
-        .and_then(|addr| addr.as_list())
-        .map(parse_addrs)
-        .unwrap_or_default();
+    // An encoded word can be at most 75 characters long, leaving 45 bytes of
+    // text once `=?UTF-8?B?` and `?=` are added and the rest is base64 encoded
+    const MAX_ENCODED_BYTES: usize = 45;
 
-    let bcc_addresses: Vec<EmailAddress> = message
-        .bcc()
-        .and_then(|addr| addr.as_list())
-        .map(parse_addrs)
-        .unwrap_or_default();
+    if !name.is_ascii() {
+        let mut words: Vec<String> = Vec::new();
+        let mut chunk = String::new();
+        for c in name.chars() {
+            if chunk.len() + c.len_utf8() > MAX_ENCODED_BYTES {
+                words.push(format!("=?UTF-8?B?{}?=", STANDARD.encode(&chunk)));
+                chunk.clear();
+            }
+            chunk.push(c);
+        }
+        words.push(format!("=?UTF-8?B?{}?=", STANDARD.encode(&chunk)));
+        return words.join(" ");
+    }
 
-    let subject = message.subject().map(ToOwned::to_owned);
+    let is_atom_text = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~ ".contains(c);
+    if name.chars().all(is_atom_text) && !name.contains("  ") {
+        return name.to_owned();
+    }
 
-    let date = message.date().map(|d| d.to_timestamp());
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
 
-    let text_bodies: Vec<EmailText> = message
-        .text_bodies()
-        .par_bridge()
-        .map(|x| x.to_string())
-        .map(parse_text)
-        .collect();
-    let html_bodies: Vec<EmailText> = message
-        .html_bodies()
-        .par_bridge()
-        .map(|x| x.to_string())
-        .map(parse_html)
-        .collect();
+/// Email addresses written in the plain text bodies, such as "also reach out to
+/// jane@example.com", in order of appearance. Addresses already in the From, To
+/// or Cc headers are left out, and each address is returned once whatever its case.
+#[uniffi::export]
+fn extract_body_addresses(email: &Email) -> Vec<String> {
+    static ADDRESS_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)[a-z0-9][a-z0-9._%+-]*@(?:[a-z0-9](?:[a-z0-9-]*[a-z0-9])?\.)+[a-z]{2,}")
+            .expect("expression is valid")
+    });
 
-    let calendar_events: Vec<CalendarEvent> = message
-        .attachments()
-        .par_bridge()
-        .filter(|m| {
-            m.content_type().is_some_and(|typ| {
-                typ.ctype() == "text" && typ.subtype().is_some_and(|s| s == "calendar")
-            })
-        })
-        .filter_map(|m| m.text_contents())
-        .filter_map(parse_events)
-        .flatten()
+    let mut seen: HashSet<String> = email
+        .from_addresses
+        .iter()
+        .chain(&email.to_addresses)
+        .chain(&email.cc_addresses)
+        .map(|addr| addr.address.to_lowercase())
         .collect();
+    let mut addresses = Vec::new();
 
-    let markups: Vec<String> = message
-        .html_bodies()
-        .par_bridge()
-        .map(|x| x.to_string())
-        .flat_map(|x| parse_json_lds(&x))
-        .collect();
+    for body in &email.text_bodies {
+        let text = body.visible.as_deref().unwrap_or(&body.text);
+        for found in ADDRESS_RE.find_iter(text) {
+            // Skip `@mentions` like `@team@example.com` and matches cut out of a longer
+            // token, such as `me@example.co1`
+            let before = text[..found.start()].chars().next_back();
+            let after = text[found.end()..].chars().next();
+            let (local, _) = found.as_str().split_once('@').unwrap_or_default();
+            if before == Some('@')
+                || after.is_some_and(|c| c.is_alphanumeric() || matches!(c, '@' | '-' | '_'))
+                || local.ends_with('.')
+                || local.contains("..")
+            {
+                continue;
+            }
 
-    let microdata_items: Vec<MicrodataItem> = message
-        .html_bodies()
-        .par_bridge()
-        .map(|x| x.to_string())
-        .flat_map(|x| extract_microdata(&x))
-        .collect();
+            if seen.insert(found.as_str().to_lowercase()) {
+                addresses.push(found.as_str().to_owned());
+            }
+        }
+    }
 
-    let unsubscribe = extract_unsubscribe(&message);
+    addresses
+}
 
-    let content_id = message.content_id().map(ToOwned::to_owned);
-    let message_id = message.message_id().map(ToOwned::to_owned);
-    let thread_name = message.thread_name().map(ToOwned::to_owned);
-    let mime_version = message.mime_version().as_text().map(ToOwned::to_owned);
+/// Local parts and names that say nothing about who the sender is.
+const GENERIC_SENDERS: &[&str] = &[
+    "noreply",
+    "no-reply",
+    "no_reply",
+    "donotreply",
+    "do-not-reply",
+    "do_not_reply",
+    "do not reply",
+    "mailer-daemon",
+    "postmaster",
+    "notifications",
+    "notification",
+    "info",
+    "support",
+    "hello",
+    "news",
+    "newsletter",
+    "team",
+    "mail",
+    "email",
+];
 
-    let headers: Vec<Header> = message.headers_raw().map(Into::into).collect();
+/// A label for a contact: its name when it has a sensible one, otherwise one
+/// derived from the address, e.g. `John Doe` for `john.doe@example.com` or
+/// `Example` for `noreply@example.com`.
+#[uniffi::export]
+fn display_name(addr: &EmailAddress) -> String {
+    let name = addr
+        .name
+        .as_deref()
+        .map(|name| name.trim().trim_matches(['"', '\'']).trim())
+        .filter(|name| {
+            !name.is_empty()
+                && !name.eq_ignore_ascii_case(&addr.address)
+                && !name.contains('@')
+                && !GENERIC_SENDERS.contains(&name.to_lowercase().as_str())
+        });
+    if let Some(name) = name {
+        return name.to_owned();
+    }
 
-    Ok(Email {
-        from,
-        from_addresses,
-        to,
-        to_addresses,
-        cc_addresses,
-        bcc_addresses,
-        subject,
-        date,
-        content_id,
-        message_id,
-        thread_name,
-        mime_version,
-        headers,
-        text_bodies,
-        html_bodies,
-        markups,
-        calendar_events,
-        microdata_items,
-        unsubscribe,
-    })
-}
+    let (local, domain) = addr.address.rsplit_once('@').unwrap_or((&addr.address, ""));
+    let local = local.split('+').next().unwrap_or_default();
 
-fn parse_json_lds(html: &str) -> Vec<String> {
-    let document = Html::parse_document(html);
-    let selector = Selector::parse(r#"script[type="application/ld+json"]"#)
-        .expect("failed to create json ld scripts selector");
+    let source = if GENERIC_SENDERS.contains(&local.to_lowercase().as_str()) && !domain.is_empty() {
+        registrable_label(domain)
+    } else {
+        local
+    };
 
-    let mut results = Vec::new();
+    let words: Vec<String> = source
+        .split(['.', '_', '-', ' '])
+        .map(|word| word.trim_matches(|c: char| c.is_ascii_digit()))
+        .filter(|word| !word.is_empty())
+        .map(title_case)
+        .collect();
 
-    for el in document.select(&selector) {
-        if let Some(raw) = el.text().next() {
-            let trimmed = raw.trim();
+    if words.is_empty() {
+        addr.address.to_owned()
+    } else {
+        words.join(" ")
+    }
+}
 
-            match serde_json::from_str::<serde_json::Value>(trimmed) {
-                Ok(serde_json::Value::Array(arr)) => {
-                    for val in arr {
-                        if let Ok(s) = serde_json::to_string(&val) {
-                            results.push(s);
-                        }
-                    }
-                }
-                Ok(val) => {
-                    if let Ok(s) = serde_json::to_string(&val) {
-                        results.push(s);
-                    }
-                }
-                Err(_) => {
-                    // Ignore and return nothing for this script
-                }
+/// Public suffixes made of two labels, from the ICANN section of the Public Suffix
+/// List for common email countries, and from its private section for shared hosts
+/// whose subdomains belong to different owners. It's a small subset of the list:
+/// other multi-label suffixes are read as single-label ones, so their subdomains
+/// look like registrable domains of the same organization.
+const MULTI_LABEL_SUFFIXES: &[&str] = &[
+    // ICANN
+    "ac.uk",
+    "co.uk",
+    "gov.uk",
+    "ltd.uk",
+    "me.uk",
+    "net.uk",
+    "org.uk",
+    "plc.uk",
+    "com.au",
+    "edu.au",
+    "gov.au",
+    "net.au",
+    "org.au",
+    "co.nz",
+    "net.nz",
+    "org.nz",
+    "ac.jp",
+    "co.jp",
+    "go.jp",
+    "ne.jp",
+    "or.jp",
+    "co.kr",
+    "or.kr",
+    "com.cn",
+    "net.cn",
+    "org.cn",
+    "com.hk",
+    "com.sg",
+    "com.tw",
+    "com.my",
+    "com.ph",
+    "com.vn",
+    "co.id",
+    "co.th",
+    "co.in",
+    "net.in",
+    "org.in",
+    "com.pk",
+    "co.il",
+    "com.tr",
+    "com.sa",
+    "com.eg",
+    "co.za",
+    "com.ng",
+    "com.br",
+    "net.br",
+    "org.br",
+    "com.ar",
+    "com.mx",
+    "com.co",
+    // Private
+    "github.io",
+    "gitlab.io",
+    "herokuapp.com",
+    "appspot.com",
+    "blogspot.com",
+    "firebaseapp.com",
+    "web.app",
+    "netlify.app",
+    "vercel.app",
+    "pages.dev",
+    "workers.dev",
+    "azurewebsites.net",
+    "cloudfront.net",
+];
+
+/// Number of trailing labels that make up the public suffix of a domain's labels
+/// (see `MULTI_LABEL_SUFFIXES`).
+fn public_suffix_labels(labels: &[&str]) -> usize {
+    match labels {
+        [.., second, top] => {
+            let suffix = format!("{second}.{top}").to_ascii_lowercase();
+            if MULTI_LABEL_SUFFIXES.contains(&suffix.as_str()) {
+                2
+            } else {
+                1
             }
         }
+        _ => 1,
     }
+}
 
-    results
+/// The label of a domain before its public suffix (see `public_suffix_labels`).
+fn registrable_label(domain: &str) -> &str {
+    let labels: Vec<&str> = domain.split('.').filter(|l| !l.is_empty()).collect();
+    let suffix = public_suffix_labels(&labels);
+    match labels.len().checked_sub(suffix + 1) {
+        Some(index) => labels[index],
+        None => labels.first().copied().unwrap_or(domain),
+    }
 }
 
-#[derive(serde::Deserialize)]
-#[allow(non_snake_case)]
-struct GmailMessageIn {
-    id: String,
-    threadId: String,
-    labelIds: Vec<String>,
-    snippet: String,
-    sizeEstimate: u32,
-    raw: String,
-    historyId: String,
-    internalDate: String,
+/// The organizational domain of a domain, e.g. `example.co.uk` for
+/// `mail.example.co.uk` and `web.de` for `mail.web.de`, with the suffixes of
+/// `public_suffix_labels`.
+fn organizational_domain(domain: &str) -> String {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    let labels: Vec<&str> = domain.split('.').filter(|l| !l.is_empty()).collect();
+    let suffix = public_suffix_labels(&labels);
+    match labels.len().checked_sub(suffix + 1) {
+        Some(index) => labels[index..].join("."),
+        None => domain.to_owned(),
+    }
 }
 
-#[derive(uniffi::Record)]
-#[allow(non_snake_case)]
-struct GmailMessage {
-    id: String,
-    threadId: String,
-    labelIds: Vec<String>,
-    snippet: EmailText,
-    sizeEstimate: u32,
-    data: Email,
-    historyId: String,
-    internalDate: String,
+fn title_case(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| {
+            first
+                .to_uppercase()
+                .chain(chars.flat_map(char::to_lowercase))
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
-#[derive(uniffi::Record, serde::Deserialize)]
-struct GmailError {
-    code: u32,
-    message: String,
-    status: String,
-    errors: Vec<GmailErrorItem>,
+/// The decoded contents of a body part.
+///
+/// `mail_parser` decodes parts according to their Content-Transfer-Encoding, but some
+/// senders quoted-printable encode a part without declaring it. Parts without a
+/// Content-Transfer-Encoding are decoded here when they clearly are quoted-printable;
+/// declared parts were already decoded and are left alone.
+fn body_text(part: &mail_parser::MessagePart<'_>) -> String {
+    let text = part.to_string();
+    if part.content_transfer_encoding().is_none() && looks_quoted_printable(&text) {
+        decode_quoted_printable(&text)
+    } else {
+        text
+    }
 }
 
-#[derive(uniffi::Record, serde::Deserialize)]
-struct GmailErrorItem {
-    message: String,
-    domain: String,
-    reason: String,
-}
+/// Whether text has soft line breaks and most of its lines with an `=` only use it
+/// for soft breaks and `=XX` escapes. Text like `?w=200&h=200` has escape-like
+/// sequences too, so those alone aren't enough.
+fn looks_quoted_printable(text: &str) -> bool {
+    let is_escape = |rest: &[u8]| {
+        rest.is_empty()
+            || rest.len() >= 2
+                && rest[..2]
+                    .iter()
+                    .all(|b| b.is_ascii_digit() || (b'A'..=b'F').contains(b))
+    };
 
-#[derive(uniffi::Enum)]
-#[allow(clippy::large_enum_variant)]
-enum BatchResponse {
-    Success(GmailMessage),
-    Error(GmailError),
-}
+    let mut soft_breaks = 0;
+    let mut lines_with_equals = 0;
+    let mut encoded_lines = 0;
+    for line in text.lines().map(|line| line.trim_end_matches('\r')) {
+        if !line.contains('=') {
+            continue;
+        }
+        lines_with_equals += 1;
+        if line.ends_with('=') {
+            soft_breaks += 1;
+        }
+        let bytes = line.as_bytes();
+        let all_escapes = bytes
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == b'=')
+            .all(|(i, _)| is_escape(&bytes[i + 1..]));
+        if all_escapes {
+            encoded_lines += 1;
+        }
+    }
 
-#[derive(uniffi::Record)]
-struct BatchSection {
-    batch_name: String,
-    response: BatchResponse,
+    soft_breaks > 0 && encoded_lines * 2 > lines_with_equals
 }
 
-fn parse_gmail(
-    GmailMessageIn {
-        id,
-        threadId,
-        labelIds,
-        snippet,
-        sizeEstimate,
-        raw,
-        historyId,
-        internalDate,
-    }: GmailMessageIn,
-) -> Option<GmailMessage> {
-    Some(GmailMessage {
-        id,
-        threadId,
-        labelIds,
-        snippet: parse_text(snippet),
-        sizeEstimate,
-        historyId,
-        internalDate,
-        data: parse_email(raw).ok()?,
-    })
+/// Decodes quoted-printable text (RFC 2045), keeping invalid sequences as they are.
+fn decode_quoted_printable(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'=' {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        match bytes.get(i + 1..i + 3) {
+            Some([b'\r', b'\n', ..]) => i += 3,
+            Some([b'\n', ..]) => i += 2,
+            Some(hex) => match std::str::from_utf8(hex)
+                .ok()
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                None => {
+                    decoded.push(b'=');
+                    i += 1;
+                }
+            },
+            None if bytes.get(i + 1) == Some(&b'\n') => i += 2,
+            None => {
+                decoded.push(b'=');
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
-#[uniffi::export]
-fn parse_batch_response(body: String) -> Vec<BatchSection> {
-    body.split("--")
-        .par_bridge()
-        .filter_map(|section| -> Option<BatchSection> {
-            let batch_name = section.lines().next()?.trim().to_owned();
-            let json_start = section.find('{')?;
-            let json_end = section.rfind('}')?;
+/// Fills in `charset_detected` and `charset_confidence` for a body part.
+///
+/// `mail_parser` decodes parts with their declared charset when it supports it, and
+/// as UTF-8 otherwise, so a charset counts as declared when it's UTF-8, ASCII, or one
+/// `mail_parser` has a decoder for.
+fn with_charset_detection(mut body: EmailText, part: &mail_parser::MessagePart<'_>) -> EmailText {
+    let declared = part
+        .content_type()
+        .and_then(|typ| typ.attribute("charset"))
+        .map(|charset| charset.trim().to_ascii_lowercase())
+        .is_some_and(|charset| {
+            matches!(charset.as_str(), "utf-8" | "utf8" | "us-ascii" | "ascii")
+                || mail_parser::decoders::charsets::map::charset_decoder(charset.as_bytes())
+                    .is_some()
+        });
 
-            let json = &section[json_start..=json_end];
+    if !declared {
+        body.charset_detected = true;
+        body.charset_confidence = Some(charset_confidence(&body.text));
+    }
+    body
+}
 
-            let success = serde_json::from_str(json)
-                .ok()
-                .and_then(parse_gmail)
-                .map(BatchResponse::Success);
-            let error = serde_json::from_str(json).ok().map(BatchResponse::Error);
+/// Scores how likely a text was decoded with the right charset, from the share of
+/// characters that aren't U+FFFD or the start of UTF-8 read as Latin-1 (`Ã©`, `â€`).
+fn charset_confidence(text: &str) -> f32 {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return 1.0;
+    }
 
-            let response = success.or(error)?;
+    let suspicious = chars
+        .windows(2)
+        .filter(|pair| matches!(pair, ['Ã' | 'Â' | 'â', '\u{80}'..='\u{BF}' | '€' | '™']))
+        .count()
+        + chars.iter().filter(|&&c| c == '\u{FFFD}').count();
 
-            Some(BatchSection {
-                batch_name,
-                response,
-            })
-        })
-        .collect()
+    1.0 - (suspicious as f32 / chars.len() as f32).min(1.0)
+}
+
+fn parse_text(body: String) -> EmailText {
+    let escaped = html_escape::decode_html_entities(&body);
+    EmailText {
+        visible: parse_visible_text(&escaped),
+        text: escaped.into_owned(),
+        decoded_text: None,
+        content_type: "text/plain".to_owned(),
+        part_id: String::new(),
+        content_language: None,
+        truncated: false,
+        charset_detected: false,
+        charset_confidence: None,
+    }
 }
 
+static BUILTIN_REPLY_SEPARATORS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    vec![Regex::new(
+        r"On\s\w{3},\s(?:\d{1,2}|\w{3})\s(?:\d{1,2}|\w{3}),?\s\d{4}\sat\s\d{1,2}:\d{2}",
+    )
+    .expect("expression is valid")]
+});
+
+static REPLY_SEPARATORS: RwLock<Vec<Regex>> = RwLock::new(Vec::new());
+
+/// Registers an extra pattern marking the start of quoted history in plain text bodies.
+/// Registered patterns are used by `parse_visible_text` alongside the built-in ones.
 #[uniffi::export]
-fn escape_text(text: String) -> String {
-    html_escape::encode_text(&text).into_owned()
+fn register_reply_separator(regex: String) -> Return<()> {
+    let regex = Regex::new(&regex).map_err(|_| ParserError::InvalidPattern)?;
+    REPLY_SEPARATORS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(regex);
+    Ok(())
 }
 
-#[derive(uniffi::Record)]
-struct CalendarEvent {
-    uid: Option<String>,
-    summary: Option<String>,
-    status: Option<CalendarEventStatus>,
-    url: Option<String>,
-    google_conference_link: Option<String>,
-    location: Option<String>,
-    timestamp: Option<i64>,
-    last_modified: Option<i64>,
-    created: Option<i64>,
-    start: Option<i64>,
-    end: Option<i64>,
+#[uniffi::export]
+fn parse_visible_text(body: &str) -> Option<String> {
+    let registered = REPLY_SEPARATORS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    let reply_start = BUILTIN_REPLY_SEPARATORS
+        .iter()
+        .chain(registered.iter())
+        .filter_map(|reply_sep_re| reply_sep_re.find(body))
+        .map(|m| m.start())
+        .min()?;
+
+    Some(body[..reply_start].trim().to_owned())
 }
 
-#[derive(uniffi::Enum)]
-enum CalendarEventStatus {
-    /// Indicates event is tentative.
-    Tentative,
-    /// Indicates event is definite.
-    Confirmed,
-    /// Indicates event was cancelled.
-    Cancelled,
+fn parse_html(body: String, strip_tags: bool, visible_only: bool) -> EmailText {
+    let visible = parse_visible_html(&body);
+    let body = match &visible {
+        Some(visible) if visible_only => visible.clone(),
+        _ => body,
+    };
+    let decoded_text = if strip_tags {
+        html_to_text(&body)
+    } else {
+        html_escape::decode_html_entities(&body).into_owned()
+    };
+
+    EmailText {
+        visible,
+        decoded_text: Some(decoded_text),
+        content_type: "text/html".to_owned(),
+        part_id: String::new(),
+        content_language: None,
+        text: body,
+        truncated: false,
+        charset_detected: false,
+        charset_confidence: None,
+    }
 }
 
-impl From<icalendar::EventStatus> for CalendarEventStatus {
-    fn from(value: icalendar::EventStatus) -> Self {
-        match value {
-            icalendar::EventStatus::Tentative => Self::Tentative,
-            icalendar::EventStatus::Confirmed => Self::Confirmed,
-            icalendar::EventStatus::Cancelled => Self::Cancelled,
+/// Cuts `text` down to its first `max_chars` grapheme clusters.
+fn truncate_graphemes(text: &mut String, max_chars: usize) -> bool {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    match text.grapheme_indices(true).nth(max_chars) {
+        Some((end, _)) => {
+            text.truncate(end);
+            true
         }
+        None => false,
     }
 }
 
-fn parse_events(body: &str) -> Option<Vec<CalendarEvent>> {
-    let calendar: Calendar = body.parse().ok()?;
+fn truncate_email_text(body: &mut EmailText, max_chars: usize) {
+    let text_truncated = truncate_graphemes(&mut body.text, max_chars);
+    let visible_truncated = body
+        .visible
+        .as_mut()
+        .is_some_and(|visible| truncate_graphemes(visible, max_chars));
+    let decoded_truncated = body
+        .decoded_text
+        .as_mut()
+        .is_some_and(|decoded| truncate_graphemes(decoded, max_chars));
 
-    Some(
-        calendar
-            .components
-            .into_iter()
-            .filter_map(parse_calendar_event)
-            .collect(),
-    )
+    body.truncated |= text_truncated || visible_truncated || decoded_truncated;
 }
 
-fn parse_calendar_event(comp: icalendar::CalendarComponent) -> Option<CalendarEvent> {
-    let event = comp.as_event()?;
+/// Strips any stack of leading reply/forward prefixes (`Re: Fwd: AW: ...`, including
+/// numbered ones like `Re[2]:`) from a subject and collapses its whitespace.
+/// Single-letter prefixes like Italian Outlook's `R:` and `I:` are kept, as they
+/// can't be told apart from subjects like `R: the language`.
+#[uniffi::export]
+fn normalize_subject(subject: &str) -> String {
+    static PREFIX_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"(?i)^\s*(?:re|fwd?|fw|aw|wg|sv|vs|antw|doorst|rif|tr|rv|res|enc|odp|pd|ynt|ilt|vl|atb|vb|回复|回覆|转发|轉寄|答复|返信|転送)(?:\s*[\[(]\d+[\])])?\s*[:：]",
+        )
+        .expect("expression is valid")
+    });
 
-    // Uses LAST_MODIFIED instead
-    let last_modified1 = event.get_last_modified().map(|x| x.timestamp_millis());
-    let last_modified2 = event
-        .properties()
-        .get("LAST-MODIFIED")
-        .and_then(DatePerhapsTime::from_property)
-        .and_then(get_timestamp);
+    let mut rest = subject;
+    while let Some(prefix) = PREFIX_RE.find(rest) {
+        rest = &rest[prefix.end()..];
+    }
 
-    let last_modified = last_modified1.or(last_modified2);
+    rest.split_whitespace().collect::<Vec<_>>().join(" ")
+}
 
-    Some(CalendarEvent {
-        uid: event.get_uid().map(|s| s.to_owned()),
-        summary: event.get_summary().map(|s| s.to_owned()),
-        status: event.get_status().map(|s| s.into()),
-        url: event.get_url().map(|x| x.to_owned()),
-        google_conference_link: event
-            .property_value("X-GOOGLE-CONFERENCE")
-            .map(|x| x.to_owned()),
-        location: event.get_location().map(|x| x.to_string()),
-        timestamp: event.get_timestamp().map(|x| x.timestamp_millis()),
-        last_modified,
-        created: event.get_created().map(|x| x.timestamp_millis()),
-        start: event.get_start().and_then(get_timestamp),
-        end: event.get_end().and_then(get_timestamp),
-    })
+/// A key for grouping emails into threads by subject when there's no Gmail
+/// `threadId`, like Gmail does: the subject without reply/forward prefixes,
+/// lowercased and with its whitespace collapsed. It's meant for grouping and
+/// search indexes, not display; use `Email::subject_normalized` for that.
+#[uniffi::export]
+fn thread_key(email: &Email) -> String {
+    normalize_subject(email.subject.as_deref().unwrap_or_default()).to_lowercase()
 }
 
-fn get_timestamp(x: icalendar::DatePerhapsTime) -> Option<i64> {
-    match x {
-        DatePerhapsTime::DateTime(calendar_date_time) => match calendar_date_time {
-            icalendar::CalendarDateTime::Floating(naive_date_time) => {
-                let timestamp = naive_date_time.and_utc().timestamp_millis();
-                Some(timestamp)
+/// Mobile client and carrier footers, matched case-insensitively against whole lines.
+/// Add new ones here.
+const KNOWN_FOOTERS: &[&str] = &[
+    r"sent from my \S.{0,40}",
+    r"sent from (?:yahoo mail|outlook|mail|gmail|aol|aol mail|proton mail)(?: (?:for|on) \S.{0,20})?",
+    r"sent with proton mail secure email\.?",
+    r"sent via \S.{0,40}",
+    r"get outlook for (?:ios|android|mac|windows)",
+    r"envoyé de mon \S.{0,30}",
+    r"von meinem \S.{0,30} gesendet",
+    r"enviado desde mi \S.{0,30}",
+    r"inviato da(?: mio)? \S.{0,30}",
+];
+
+static FOOTER_RES: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    KNOWN_FOOTERS
+        .iter()
+        .map(|footer| Regex::new(&format!("(?i)^{footer}$")).expect("expression is valid"))
+        .collect()
+});
+
+#[derive(uniffi::Record, Debug, PartialEq)]
+struct QuotedLine {
+    /// Number of `>` quote markers, 0 for lines that aren't quoted
+    depth: u32,
+    /// The line without its quote markers
+    content: String,
+}
+
+/// Splits a plain text body into lines with their quote depth, so `> > >` and `>>>`
+/// are both depth 3. The space following the last marker is removed along with them.
+#[uniffi::export]
+fn quote_levels(text: &str) -> Vec<QuotedLine> {
+    text.lines()
+        .map(|line| {
+            let mut depth = 0;
+            let mut rest = line;
+            loop {
+                let trimmed = rest.trim_start_matches([' ', '\t']);
+                match trimmed.strip_prefix('>') {
+                    Some(after) => {
+                        depth += 1;
+                        rest = after;
+                    }
+                    None => break,
+                }
             }
-            icalendar::CalendarDateTime::Utc(date_time) => {
-                let timestamp = date_time.timestamp_millis();
-                Some(timestamp)
+            if depth > 0 {
+                rest = rest.strip_prefix(' ').unwrap_or(rest);
             }
-            icalendar::CalendarDateTime::WithTimezone { date_time, tzid } => {
-                let tz: chrono_tz::Tz = tzid.parse().ok()?;
-                let local_dt = tz.from_local_datetime(&date_time).single()?;
-                let timestamp = local_dt.timestamp_millis();
-                Some(timestamp)
+
+            QuotedLine {
+                depth,
+                content: rest.to_owned(),
             }
-        },
-        DatePerhapsTime::Date(naive_date) => {
-            let datetime = naive_date.and_hms_opt(0, 0, 0)?;
-            let timestamp = Utc.from_utc_datetime(&datetime).timestamp_millis();
-            Some(timestamp)
+        })
+        .collect()
+}
+
+/// Removes trailing "Sent from my iPhone"-style footers from a plain text body.
+///
+/// Only footers at the very end are removed, and a body that is nothing but a footer
+/// is returned unchanged.
+#[uniffi::export]
+fn strip_footers(text: &str) -> String {
+    let mut lines: Vec<&str> = text.trim_end().lines().collect();
+
+    while let Some(last) = lines.last() {
+        // Outlook appends the download link, e.g. `Get Outlook for iOS<https://aka.ms/o0ukef>`
+        let last = last.trim();
+        let last = match last.find('<') {
+            Some(link_start) if last.ends_with('>') => last[..link_start].trim_end(),
+            _ => last,
+        };
+
+        if !FOOTER_RES.iter().any(|footer_re| footer_re.is_match(last)) {
+            break;
+        }
+        if lines[..lines.len() - 1].iter().all(|l| l.trim().is_empty()) {
+            break;
+        }
+
+        lines.pop();
+        while lines.last().is_some_and(|l| l.trim().is_empty()) {
+            lines.pop();
         }
     }
+
+    lines.join("\n")
 }
 
-#[derive(uniffi::Record)]
-struct MicrodataItem {
-    itemtype: Option<String>,
-    properties: HashMap<String, String>,
-    children: HashMap<String, MicrodataItem>,
+#[derive(uniffi::Enum, Debug, Clone, Copy, PartialEq)]
+pub(crate) enum NormalizationForm {
+    /// Canonical composition, the form most text is sent in
+    Nfc,
+    /// Canonical decomposition
+    Nfd,
+    /// Compatibility composition, which also folds variants such as `ﬁ` into `fi`
+    Nfkc,
+    /// Compatibility decomposition
+    Nfkd,
 }
 
-fn extract_microdata_items(element: &scraper::ElementRef) -> MicrodataItem {
-    let itemtype = element.value().attr("itemtype").map(String::from);
-    let mut properties = HashMap::new();
-    let mut children = HashMap::new();
+/// Converts a text to a Unicode normalization form.
+#[uniffi::export]
+fn normalize_unicode(text: &str, form: NormalizationForm) -> String {
+    use unicode_normalization::UnicodeNormalization;
 
-    let prop_selector = Selector::parse("[itemprop]").unwrap();
+    match form {
+        NormalizationForm::Nfc => text.nfc().collect(),
+        NormalizationForm::Nfd => text.nfd().collect(),
+        NormalizationForm::Nfkc => text.nfkc().collect(),
+        NormalizationForm::Nfkd => text.nfkd().collect(),
+    }
+}
 
-    for prop in element.select(&prop_selector) {
-        if prop.value().attr("itemscope").is_some() {
-            if let Some(prop_name) = prop.value().attr("itemprop") {
-                let child = extract_microdata_items(&prop);
-                children.insert(prop_name.to_owned(), child);
+#[derive(uniffi::Enum, Debug, PartialEq)]
+enum TextDirection {
+    Ltr,
+    Rtl,
+    /// No strong directional character was found
+    Neutral,
+}
+
+/// Detects the direction of a text from its first strong directional character,
+/// as described by the Unicode bidirectional algorithm.
+#[uniffi::export]
+fn text_direction(text: &str) -> TextDirection {
+    match unicode_bidi::get_base_direction_full(text) {
+        unicode_bidi::Direction::Ltr => TextDirection::Ltr,
+        unicode_bidi::Direction::Rtl => TextDirection::Rtl,
+        unicode_bidi::Direction::Mixed => TextDirection::Neutral,
+    }
+}
+
+#[derive(uniffi::Enum, Debug, PartialEq)]
+enum MessageSecurity {
+    Signed,
+    Encrypted,
+    SignedAndEncrypted,
+}
+
+/// Tells whether a message is S/MIME or PGP signed or encrypted from its MIME
+/// structure, or from inline PGP armor in its text bodies. Nothing is verified or
+/// decrypted, and signatures inside an encrypted payload can't be seen.
+fn message_security(
+    message: &mail_parser::Message<'_>,
+    text_bodies: &[EmailText],
+) -> Option<MessageSecurity> {
+    let mut signed = false;
+    let mut encrypted = false;
+
+    for typ in message.parts.iter().filter_map(|part| part.content_type()) {
+        let ctype = typ.ctype().to_ascii_lowercase();
+        let subtype = typ.subtype().unwrap_or_default().to_ascii_lowercase();
+        match (ctype.as_str(), subtype.as_str()) {
+            ("multipart", "signed") => signed = true,
+            ("multipart", "encrypted") | ("application", "pgp-encrypted") => encrypted = true,
+            ("application", "pkcs7-mime" | "x-pkcs7-mime") => {
+                // Without smime-type, a pkcs7-mime part is most likely enveloped
+                match typ.attribute("smime-type").map(str::to_ascii_lowercase) {
+                    Some(smime_type) if smime_type == "signed-data" => signed = true,
+                    _ => encrypted = true,
+                }
             }
-        } else if let Some(prop_name) = prop.value().attr("itemprop") {
-            let value = prop.text().collect::<Vec<_>>().join(" ").trim().to_string();
-            let value = prop
-                .attr("content")
-                .or(prop.attr("href"))
-                .map(|c| c.to_owned())
-                .unwrap_or(value);
+            _ => {}
+        }
+    }
 
-            properties.insert(prop_name.to_owned(), value);
+    for body in text_bodies {
+        for line in body.text.lines().map(str::trim_end) {
+            match line {
+                "-----BEGIN PGP SIGNED MESSAGE-----" => signed = true,
+                "-----BEGIN PGP MESSAGE-----" => encrypted = true,
+                _ => {}
+            }
         }
     }
 
-    MicrodataItem {
-        itemtype,
-        properties,
-        children,
+    match (signed, encrypted) {
+        (true, true) => Some(MessageSecurity::SignedAndEncrypted),
+        (true, false) => Some(MessageSecurity::Signed),
+        (false, true) => Some(MessageSecurity::Encrypted),
+        (false, false) => None,
     }
 }
 
-fn extract_microdata(html: &str) -> Vec<MicrodataItem> {
-    let document = Html::parse_document(html);
-    let root_selector = Selector::parse("[itemscope]").unwrap();
+#[derive(uniffi::Enum, Debug, PartialEq)]
+enum MessagePriority {
+    High,
+    Normal,
+    Low,
+}
 
-    document
-        .select(&root_selector)
-        // Skip nested scopes; only process top-level items here
-        .filter(|el| {
-            el.ancestors()
-                .filter_map(scraper::ElementRef::wrap)
-                .all(|e| e.value().attr("itemscope").is_none())
-        })
-        .map(|el| extract_microdata_items(&el))
+/// Reads the priority of a message. Importance is preferred when the headers
+/// disagree, as it's the header mail clients set when the user flags a message.
+fn parse_priority(message: &mail_parser::Message<'_>) -> Option<MessagePriority> {
+    let header = |name: &str| {
+        message
+            .header_raw(name)
+            .map(|value| value.trim().to_ascii_lowercase())
+    };
+
+    let importance = header("Importance").and_then(|value| match value.as_str() {
+        "high" => Some(MessagePriority::High),
+        "normal" => Some(MessagePriority::Normal),
+        "low" => Some(MessagePriority::Low),
+        _ => None,
+    });
+    // X-Priority is usually followed by a comment, as in `1 (Highest)`
+    let x_priority = header("X-Priority").and_then(|value| match value.get(..1) {
+        Some("1" | "2") => Some(MessagePriority::High),
+        Some("3") => Some(MessagePriority::Normal),
+        Some("4" | "5") => Some(MessagePriority::Low),
+        _ => None,
+    });
+    let priority = header("Priority").and_then(|value| match value.as_str() {
+        "urgent" => Some(MessagePriority::High),
+        "normal" => Some(MessagePriority::Normal),
+        "non-urgent" => Some(MessagePriority::Low),
+        _ => None,
+    });
+
+    importance.or(x_priority).or(priority)
+}
+
+/// Reads the `d=` tag of every DKIM-Signature header.
+fn dkim_domains(message: &mail_parser::Message<'_>) -> Vec<String> {
+    message
+        .headers_raw()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("DKIM-Signature"))
+        .filter_map(|(_, value)| header_tag(value, "d"))
+        .map(|domain| domain.to_lowercase())
         .collect()
 }
 
-#[derive(uniffi::Record)]
-struct Unsubscribe {
-    get: Option<String>,
-    website: Option<String>,
-    post: Option<UnsubscribePost>,
-    email: Option<UnsubscribeEmail>,
+/// Reads a tag from a `name=value; ...` tag list header such as DKIM-Signature or
+/// BIMI-Location, removing folding whitespace from the value.
+fn header_tag(header: &str, name: &str) -> Option<String> {
+    header.split(';').find_map(|tag| {
+        let (tag, value) = tag.split_once('=')?;
+        let value: String = value.split_whitespace().collect();
+        (tag.trim() == name && !value.is_empty()).then_some(value)
+    })
 }
 
-#[derive(uniffi::Record)]
-struct UnsubscribePost {
-    url: String,
-    body: String,
+/// Header signals for deciding how much to trust the sender
+#[derive(uniffi::Record, Debug, PartialEq)]
+struct SenderSignals {
+    /// Whether the receiving server's Authentication-Results reported `dmarc=pass`,
+    /// or `dkim=pass` with a signature aligned with the From domain
+    authenticated: bool,
+    /// Whether the email was sent to a list, from `Precedence: bulk|list|junk`,
+    /// List-Id or List-Unsubscribe
+    bulk: bool,
+    /// Whether the From address is a no-reply one like `no-reply@` or `donotreply+x@`
+    no_reply: bool,
+    /// Whether the email has a List-Unsubscribe header
+    has_unsubscribe: bool,
+    /// Lowercased domain of the From address
+    from_domain: Option<String>,
 }
 
-#[derive(uniffi::Record)]
-struct UnsubscribeEmail {
-    email: String,
-    headers: Vec<Header>,
+fn sender_signals(
+    message: &mail_parser::Message<'_>,
+    from: &EmailAddressWithText,
+    dkim_aligned: Option<bool>,
+) -> SenderSignals {
+    let auth_result = |method: &str| {
+        // The topmost header was added by the receiving server, the others can't be trusted
+        let (_, value) = message
+            .headers_raw()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Authentication-Results"))?;
+        value.split(';').skip(1).find_map(|result| {
+            let (name, result) = result.split_whitespace().next()?.split_once('=')?;
+            name.eq_ignore_ascii_case(method)
+                .then(|| result.to_ascii_lowercase())
+        })
+    };
+    let passed = |method: &str| auth_result(method).as_deref() == Some("pass");
+    let authenticated = passed("dmarc") || (passed("dkim") && dkim_aligned == Some(true));
+
+    let has_unsubscribe = message.header_raw("List-Unsubscribe").is_some();
+    let precedence = message
+        .header_raw("Precedence")
+        .map(|value| value.trim().to_ascii_lowercase());
+    let bulk = matches!(precedence.as_deref(), Some("bulk" | "list" | "junk"))
+        || message.header_raw("List-Id").is_some()
+        || has_unsubscribe;
+
+    let (local, domain) = from.address.rsplit_once('@').unwrap_or((&from.address, ""));
+    let local: String = local
+        .chars()
+        .filter(|c| !matches!(c, '-' | '_' | '.'))
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let no_reply = local.starts_with("noreply") || local.starts_with("donotreply");
+    let from_domain = (!domain.is_empty()).then(|| domain.to_ascii_lowercase());
+
+    SenderSignals {
+        authenticated,
+        bulk,
+        no_reply,
+        has_unsubscribe,
+        from_domain,
+    }
 }
 
-fn extract_unsubscribe(message: &mail_parser::Message<'_>) -> Unsubscribe {
-    let list_unsubscribe = message
-        .header_raw("list-unsubscribe")
-        .unwrap_or_default()
-        .trim()
-        .to_owned()
-        .decode_header();
+/// Detects out-of-office and other automatic replies from the headers their
+/// senders add: `Auto-Submitted: auto-replied` (RFC 3834), `X-Autoreply`,
+/// `X-Autorespond` and `Precedence: auto_reply`.
+fn is_auto_reply(message: &mail_parser::Message<'_>) -> bool {
+    let header = |name: &str| {
+        message
+            .header_raw(name)
+            .map(|value| value.trim().to_ascii_lowercase())
+    };
 
-    if list_unsubscribe.is_empty() {
-        return Unsubscribe {
-            get: None,
-            post: None,
-            email: None,
-            website: None,
-        };
+    // The value may be followed by parameters, as in `auto-replied; owner-email=...`
+    let auto_submitted = header("Auto-Submitted")
+        .is_some_and(|value| value.split(';').next() == Some("auto-replied"));
+    let autoreply = header("X-Autoreply").is_some_and(|value| value != "no");
+    let autorespond = header("X-Autorespond").is_some();
+    let precedence = header("Precedence").is_some_and(|value| value == "auto_reply");
+
+    auto_submitted || autoreply || autorespond || precedence
+}
+
+#[uniffi::export]
+fn parse_visible_html(body: &str) -> Option<String> {
+    if !body.contains("gmail_quote_container") {
+        return None;
     }
 
-    let mut urls = list_unsubscribe
-        .split(",")
-        .map(|x| x.trim())
-        .map(|x| x.trim_start_matches('<'))
-        .map(|x| x.trim_end_matches('>'))
-        .filter_map(|x| Url::parse(x).ok());
+    rewrite_str(
+        body,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                lol_html::element!(".gmail_quote", |el| {
+                    el.remove();
+                    Ok(())
+                }),
+                lol_html::element!(".gmail_quote_container", |el| {
+                    el.remove();
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::new()
+        },
+    )
+    .ok()
+}
 
-    let url = urls.find(|u| u.scheme() == "http" || u.scheme() == "https");
-    let list_unsubscribe_post = message
-        .header_raw("list-unsubscribe-post")
-        .map(|x| x.trim().to_owned().decode_header());
+#[uniffi::export]
+fn clean_html(body: String) -> Option<String> {
+    rewrite_str(
+        &body,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                lol_html::element!(
+                    ".gmail_quote, .gmail_quote_container, head, meta, title, link, script, style, img",
+                    |el| {
+                        el.remove();
+                        Ok(())
+                    }
+                ),
+                lol_html::element!("*[style]", |el| {
+                    if let Some(style) = el.get_attribute("style") {
+                        if style.contains("display:none") || style.contains("visibility:hidden") {
+                            el.remove();
+                        }
+                    }
 
-    let get = match (&url, &list_unsubscribe_post) {
-        (Some(url), None) => Some(url.to_string()),
-        _ => None,
-    };
+                    el.remove_attribute("style");
+                    Ok(())
+                }),
+                lol_html::element!("*[class]", |el| {
+                    el.remove_attribute("class");
+                    Ok(())
+                }),
+                lol_html::element!("html, body, table, tbody, tr, td, div", |el| {
+                    el.remove_and_keep_content();
+                    Ok(())
+                }),
+                lol_html::comments!("*", |c| {
+                    c.remove();
+                    Ok(())
+                }),
+            ],
+            document_content_handlers: vec![lol_html::doc_comments!(|c| {
+                c.remove();
+                Ok(())
+            })],
 
-    let post = match (&url, &list_unsubscribe_post) {
-        (Some(url), Some(post)) => Some(UnsubscribePost {
-            url: url.to_string(),
-            body: post.to_owned(),
-        }),
-        _ => None,
-    };
+            ..RewriteStrSettings::new()
+        },
+    )
+    .ok()
+    .map(|x| x.trim().replace("\r\n", "").replace("\n", ""))
+}
 
-    let email = urls.find(|u| u.scheme() == "mailto").map(|url| {
-        let headers = url
-            .query_pairs()
-            .into_iter()
-            .map(|(name, value)| Header {
-                name: name.into_owned(),
-                value: value.into_owned(),
-            })
-            .collect();
+#[derive(uniffi::Record)]
+struct BlockedHtml {
+    html: String,
+    /// How many remote URLs were neutralized, for prompts like "3 images blocked"
+    blocked_count: u32,
+}
 
-        UnsubscribeEmail {
-            email: url.path().to_owned(),
-            headers,
-        }
+/// Whether a URL would be fetched from the network, as opposed to `cid:` and
+/// `data:` content shipped with the email
+fn is_remote_url(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    url.starts_with("http:") || url.starts_with("https:") || url.starts_with("//")
+}
+
+/// Replaces the remote `url()`s and `@import`s of a stylesheet or `style`
+/// attribute with `none`, which browsers ignore.
+fn block_css_urls(css: &str, blocked: &Cell<u32>) -> String {
+    static URL_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)\s]*))\s*\)|@import\s+(?:"([^"]*)"|'([^']*)')"#)
+            .expect("expression is valid")
     });
 
-    let website = message
-        .html_bodies()
-        .map(|x| x.to_string())
-        .find_map(|html| {
-            let document = Html::parse_document(&html);
-            let anchor_selector = Selector::parse("a").unwrap();
-
-            let keywords = [
-                "unsubscribe",
-                "opt out",
-                "opt-out",
-                "email preferences",
-                "email settings",
-                "manage preferences",
-                "manage subscription",
-                "update preferences",
-                "stop receiving",
-                "cancel subscription",
-                "subscription settings",
-            ];
-
-            let href = document
-                .select(&anchor_selector)
-                .filter(|el| {
-                    let contains_keywords = el.text().any(|x| {
-                        let lower = x.to_lowercase();
-                        keywords.iter().any(|keyword| lower.contains(keyword))
-                    });
-
-                    let parent_contains_keywords = el
-                        .parent()
-                        .and_then(scraper::ElementRef::wrap)
-                        .map(|n| {
-                            n.text().any(|x| {
-                                let lower = x.to_lowercase();
-                                keywords.iter().any(|keyword| lower.contains(keyword))
-                            })
-                        })
-                        .unwrap_or_default();
-
-                    contains_keywords || parent_contains_keywords
-                })
-                .find_map(|el| {
-                    el.attr("href").and_then(|href| {
-                        let href = href.trim();
-                        if href.starts_with("http://") || href.starts_with("https://") {
-                            Some(href.to_owned())
-                        } else {
-                            None
+    URL_RE
+        .replace_all(css, |captures: &regex::Captures| {
+            let url = captures
+                .iter()
+                .skip(1)
+                .find_map(|group| group)
+                .map_or("", |group| group.as_str());
+            if !is_remote_url(url) {
+                return captures[0].to_owned();
+            }
+
+            blocked.set(blocked.get() + 1);
+            if captures[0].starts_with('@') {
+                "@import none".to_owned()
+            } else {
+                "none".to_owned()
+            }
+        })
+        .into_owned()
+}
+
+/// Neutralizes the remote content of an HTML body for privacy-preserving or
+/// offline viewing: images and other embeds, `background` attributes, stylesheet
+/// links and `url()`s in CSS. Each blocked attribute is moved to a `data-blocked-`
+/// attribute of the same name, like `data-blocked-src`, so it can be restored once
+/// the user opts in. Inline `data:` and `cid:` content is kept. `None` when the
+/// HTML couldn't be rewritten.
+#[uniffi::export]
+fn block_remote_content(html: String) -> Option<BlockedHtml> {
+    let blocked = Cell::new(0);
+    let block_attribute = |el: &mut lol_html::html_content::Element, name: &str| {
+        let Some(value) = el.get_attribute(name) else {
+            return Ok(());
+        };
+        let is_remote = if name == "srcset" {
+            value
+                .split(',')
+                .any(|candidate| is_remote_url(candidate.trim_start()))
+        } else {
+            is_remote_url(&value)
+        };
+        if is_remote {
+            el.set_attribute(&format!("data-blocked-{name}"), &value)?;
+            el.remove_attribute(name);
+            blocked.set(blocked.get() + 1);
+        }
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+    };
+    let style = RefCell::new(String::new());
+
+    let html = rewrite_str(
+        &html,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                lol_html::element!(
+                    "img, input, video, audio, source, track, iframe, embed",
+                    |el| {
+                        block_attribute(el, "src")?;
+                        block_attribute(el, "srcset")
+                    }
+                ),
+                lol_html::element!("video[poster]", |el| block_attribute(el, "poster")),
+                lol_html::element!("object[data]", |el| block_attribute(el, "data")),
+                lol_html::element!("*[background]", |el| block_attribute(el, "background")),
+                lol_html::element!("link[href]", |el| {
+                    let is_stylesheet = el
+                        .get_attribute("rel")
+                        .is_some_and(|rel| rel.to_ascii_lowercase().contains("stylesheet"));
+                    if is_stylesheet {
+                        block_attribute(el, "href")?;
+                    }
+                    Ok(())
+                }),
+                lol_html::element!("*[style]", |el| {
+                    if let Some(value) = el.get_attribute("style") {
+                        let count = blocked.get();
+                        let neutralized = block_css_urls(&value, &blocked);
+                        if blocked.get() > count {
+                            el.set_attribute("data-blocked-style", &value)?;
+                            el.set_attribute("style", &neutralized)?;
                         }
-                    })
-                });
-            href
-        });
+                    }
+                    Ok(())
+                }),
+                // Text arrives in chunks, which could split a URL
+                lol_html::text!("style", |chunk| {
+                    style.borrow_mut().push_str(chunk.as_str());
+                    if chunk.last_in_text_node() {
+                        let css = std::mem::take(&mut *style.borrow_mut());
+                        chunk.replace(&block_css_urls(&css, &blocked), ContentType::Html);
+                    } else {
+                        chunk.remove();
+                    }
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::new()
+        },
+    )
+    .ok()?;
 
-    Unsubscribe {
-        get,
-        post,
-        email,
-        website,
-    }
+    Some(BlockedHtml {
+        html,
+        blocked_count: blocked.get(),
+    })
 }
 
-#[cfg(test)]
-mod test {
-    use super::parse_batch_response;
+#[uniffi::export]
+fn parse_email(raw: String) -> Return<Email> {
+    parse_email_with_options(raw, ParseOptions::default())
+}
 
-    #[test]
-    fn do_test() {
-        let responses = std::fs::read_dir("responses/allspark")
-            .unwrap()
-            .filter_map(|x| x.ok())
-            .map(|x| x.path());
+#[uniffi::export]
+fn parse_email_with_options(raw: String, options: ParseOptions) -> Return<Email> {
+    let limits = options.limits.unwrap_or_default();
+    ParseLimits::check(limits.max_bytes, raw.len() / 4 * 3)?;
 
-        for path in responses {
-            let file = std::fs::read_to_string(path).unwrap();
-            parse_batch_response(file);
+    let raw = base64_decode(
+        &raw,
+        options.base64_variant.unwrap_or(Base64Variant::UrlSafe),
+    )?;
+    if limits.max_parts.is_some() {
+        ParseLimits::check(limits.max_parts, count_mime_parts(raw.as_bytes()))?;
+    }
+    let parser = MessageParser::default();
+    let message = parser.parse(&raw).ok_or(ParserError::EmailParseFailed)?;
+
+    ParseLimits::check(limits.max_parts, message.parts.len())?;
+    if limits.max_html_nodes.is_some() {
+        let tags: usize = message
+            .html_bodies()
+            .filter_map(|part| part.text_contents())
+            .map(|html| html.matches('<').count())
+            .sum();
+        ParseLimits::check(limits.max_html_nodes, tags)?;
+    }
+
+    let (mut from, from_addresses) =
+        parse_address_header(&message, HeaderName::From, ParserError::NoFromHeader)?;
+    let from_is_multiple = from_addresses.iter().filter(|addr| !addr.is_group).count() > 1;
+    if from_is_multiple {
+        let sender = message
+            .sender()
+            .and_then(|sender| sender.first())
+            .and_then(|sender| sender.address.as_deref());
+        let author = sender.and_then(|sender| {
+            from_addresses
+                .iter()
+                .find(|addr| addresses_equal(&addr.address, sender))
+        });
+        if let Some(author) = author {
+            from.name = author.name.clone();
+            from.address = author.address.clone();
+        }
+    }
+    let (to, to_addresses) =
+        parse_address_header(&message, HeaderName::To, ParserError::NoToHeader)?;
+
+    let cc_addresses: Vec<EmailAddress> = message.cc().map(parse_address).unwrap_or_default();
+
+    let bcc_addresses: Vec<EmailAddress> = message.bcc().map(parse_address).unwrap_or_default();
+
+    let subject = message
+        .subject()
+        .map(|subject| match options.subject_normalization {
+            Some(form) => normalize_unicode(subject, form),
+            None => subject.to_owned(),
+        });
+    let subject_direction = subject.as_deref().map(text_direction);
+    let subject_normalized = subject.as_deref().map(normalize_subject);
+
+    let date = parse_date(&message);
+    let date_raw = message
+        .header_raw(HeaderName::Date)
+        .map(|x| x.trim().to_owned());
+    let date_offset_seconds = message
+        .date()
+        .filter(|d| d.is_valid())
+        .filter(|d| !(d.tz_before_gmt && d.tz_hour == 0 && d.tz_minute == 0))
+        .map(|d| {
+            let offset = d.tz_hour as i32 * 3600 + d.tz_minute as i32 * 60;
+            if d.tz_before_gmt {
+                -offset
+            } else {
+                offset
+            }
+        });
+    let priority = parse_priority(&message);
+    let is_auto_reply = is_auto_reply(&message);
+
+    let dkim_domains = dkim_domains(&message);
+    let bimi_logo_url = message
+        .header_raw("BIMI-Location")
+        .and_then(|value| header_tag(value, "l"));
+    let is_aligned = |domain: &String| {
+        from.address
+            .rsplit_once('@')
+            .is_some_and(|(_, from)| organizational_domain(from) == organizational_domain(domain))
+    };
+    let dkim_aligned = (!dkim_domains.is_empty()).then(|| dkim_domains.iter().any(is_aligned));
+    let dkim_domain = dkim_domains
+        .iter()
+        .find(|domain| is_aligned(domain))
+        .or(dkim_domains.first())
+        .cloned();
+    let sender_signals = sender_signals(&message, &from, dkim_aligned);
+
+    let part_ids = part_ids(&message);
+    let with_part = |mut body: EmailText, id: usize, part: &mail_parser::MessagePart<'_>| {
+        if let Some(typ) = part.content_type() {
+            body.content_type = match typ.subtype() {
+                Some(subtype) => format!("{}/{}", typ.ctype(), subtype),
+                None => typ.ctype().to_owned(),
+            }
+            .to_lowercase();
         }
+        body.part_id = part_ids[id].clone();
+        body.content_language = first_language_tag(part.content_language());
+        with_charset_detection(body, part)
+    };
+
+    let mut text_bodies: Vec<EmailText> = message
+        .text_body
+        .par_iter()
+        .filter_map(|&id| message.parts.get(id).map(|part| (id, part)))
+        .map(|(id, part)| {
+            let mut body = parse_text(body_text(part));
+            if let Some(visible) = body.visible.as_ref().filter(|_| options.visible_only) {
+                body.text = visible.clone();
+            }
+            with_part(body, id, part)
+        })
+        .collect();
+    let mut html_bodies: Vec<EmailText> = message
+        .html_body
+        .par_iter()
+        .filter_map(|&id| message.parts.get(id).map(|part| (id, part)))
+        .map(|(id, part)| {
+            let body = parse_html(
+                body_text(part),
+                options.decoded_text_strip_tags,
+                options.visible_only,
+            );
+            with_part(body, id, part)
+        })
+        .collect();
+
+    if let Some(preview_chars) = options.preview_chars {
+        let preview_chars = preview_chars as usize;
+        text_bodies
+            .iter_mut()
+            .chain(html_bodies.iter_mut())
+            .for_each(|body| truncate_email_text(body, preview_chars));
+    }
+
+    let mut calendar_events: Vec<CalendarEvent> = message
+        .attachments()
+        .par_bridge()
+        .filter(|m| is_calendar_part(m))
+        .filter_map(|m| m.text_contents())
+        .filter_map(parse_events)
+        .flatten()
+        .collect();
+
+    let markups: Vec<String> = message
+        .html_bodies()
+        .par_bridge()
+        .map(body_text)
+        .flat_map(|x| parse_json_lds(&x, options.schema_org_only))
+        .collect();
+
+    let microdata_items: Vec<MicrodataItem> = message
+        .html_bodies()
+        .par_bridge()
+        .map(body_text)
+        .flat_map(|x| extract_microdata(&x))
+        .collect();
+
+    let markup_events: Vec<CalendarEvent> = markups
+        .iter()
+        .filter_map(|markup| serde_json::from_str::<serde_json::Value>(markup).ok())
+        .filter_map(|value| markup_calendar_event(&value))
+        .chain(microdata_items.iter().filter_map(microdata_calendar_event))
+        .collect();
+    for event in markup_events {
+        let is_duplicate = calendar_events.iter().any(|existing| {
+            let same_uid = existing.uid.is_some() && existing.uid == event.uid;
+            let same_summary_start = existing.summary.is_some()
+                && existing.summary == event.summary
+                && existing.start == event.start;
+            same_uid || same_summary_start
+        });
+        if !is_duplicate {
+            calendar_events.push(event);
+        }
+    }
+
+    let images: Vec<EmailImage> = message
+        .html_bodies()
+        .par_bridge()
+        .map(body_text)
+        .flat_map(|x| extract_images(&x))
+        .collect();
+
+    let unsubscribe = extract_unsubscribe(&message);
+    let mailing_list = extract_mailing_list(&message);
+    let attachments = extract_attachments(
+        &message,
+        options.inline_attachments_below_bytes,
+        options.inline_part_data,
+    );
+    let attachment_count = attachments.iter().filter(|a| !a.inline).count() as u32;
+    let has_attachments = attachment_count > 0;
+    let preview = email_preview(&text_bodies, &html_bodies, &calendar_events, &attachments);
+    let alt_text_summary = html_bodies
+        .first()
+        .filter(|_| options.alt_text_summary)
+        .and_then(|body| {
+            let visible_chars = html_to_text(&body.text).chars().count();
+            (visible_chars < ALT_TEXT_MAX_VISIBLE_CHARS)
+                .then(|| extract_alt_text(&body.text))
+                .flatten()
+        });
+    let received_chain = extract_received_chain(&message);
+    let security = message_security(&message, &text_bodies);
+
+    let content_id = message.content_id().map(ToOwned::to_owned);
+    let content_language = first_language_tag(message.content_language());
+    let message_id = message.message_id().map(ToOwned::to_owned);
+    let thread_name = message.thread_name().map(ToOwned::to_owned);
+    let mime_version = message.mime_version().as_text().map(ToOwned::to_owned);
+
+    let headers: Vec<Header> = message.headers_raw().map(Into::into).collect();
+
+    let warnings = if options.parse_diagnostics {
+        collect_warnings(&message)
+    } else {
+        Vec::new()
+    };
+
+    Ok(Email {
+        from,
+        from_addresses,
+        from_is_multiple,
+        to,
+        to_addresses,
+        cc_addresses,
+        bcc_addresses,
+        subject,
+        subject_direction,
+        subject_normalized,
+        date,
+        date_raw,
+        date_offset_seconds,
+        priority,
+        dkim_domain,
+        dkim_domains,
+        dkim_aligned,
+        bimi_logo_url,
+        is_auto_reply,
+        sender_signals,
+        content_id,
+        message_id,
+        thread_name,
+        content_language,
+        mime_version,
+        headers,
+        text_bodies,
+        html_bodies,
+        preview,
+        alt_text_summary,
+        markups,
+        calendar_events,
+        microdata_items,
+        images,
+        unsubscribe,
+        mailing_list,
+        attachments,
+        has_attachments,
+        attachment_count,
+        received_chain,
+        security,
+        warnings,
+    })
+}
+
+/// Re-parses the HTML and calendar parts to report what `parse_email_with_options`
+/// skipped, so the usual parse doesn't pay for it.
+fn collect_warnings(message: &mail_parser::Message<'_>) -> Vec<ParseWarning> {
+    let mut warnings = Vec::new();
+    let mut warn = |part: u32, message: String| warnings.push(ParseWarning { part, message });
+
+    for &part_id in &message.html_body {
+        let Some(html) = message.part(part_id).filter(|part| part.is_text()) else {
+            continue;
+        };
+        for (index, script) in json_ld_scripts(&body_text(html)).iter().enumerate() {
+            if let Err(err) = serde_json::from_str::<serde_json::Value>(script) {
+                warn(
+                    part_id as u32,
+                    format!("JSON-LD script #{} failed to parse: {err}", index + 1),
+                );
+            }
+        }
+    }
+
+    for (part_id, part) in message.parts.iter().enumerate() {
+        let part_id = part_id as u32;
+        if !is_calendar_part(part) {
+            continue;
+        }
+        let Some(body) = part.text_contents() else {
+            continue;
+        };
+        let calendar = match body.parse::<Calendar>() {
+            Ok(calendar) => calendar,
+            Err(err) => {
+                warn(part_id, format!("calendar part failed to parse: {err}"));
+                continue;
+            }
+        };
+        for event in calendar.components.iter().filter_map(|c| c.as_event()) {
+            for name in ["DTSTART", "DTEND", "RECURRENCE-ID"] {
+                let tzid = event
+                    .properties()
+                    .get(name)
+                    .and_then(|property| property.params().get("TZID"))
+                    .map(|tzid| tzid.value());
+                if let Some(tzid) = tzid.filter(|tzid| tzid.parse::<chrono_tz::Tz>().is_err()) {
+                    warn(
+                        part_id,
+                        format!("calendar part had unknown TZID {tzid} in {name}"),
+                    );
+                }
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Characters kept in `Email::preview`
+const PREVIEW_MAX_CHARS: usize = 200;
+
+/// The `best_text` of an email, or for emails without any, a description of their
+/// first calendar invite or attachment, like `Calendar invite: Standup`.
+fn email_preview(
+    text_bodies: &[EmailText],
+    html_bodies: &[EmailText],
+    calendar_events: &[CalendarEvent],
+    attachments: &[Attachment],
+) -> Option<String> {
+    let one_line = |text: &str| {
+        let mut line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        truncate_graphemes(&mut line, PREVIEW_MAX_CHARS);
+        Some(line).filter(|line| !line.is_empty())
+    };
+
+    let from_event = || {
+        calendar_events
+            .iter()
+            .find_map(|event| event.summary.as_deref().and_then(one_line))
+            .map(|summary| format!("Calendar invite: {summary}"))
+    };
+    let from_attachment = || {
+        attachments
+            .iter()
+            .filter(|attachment| !attachment.inline)
+            .find_map(|attachment| attachment.filename.as_deref().and_then(one_line))
+            .map(|filename| format!("Attachment: {filename}"))
+    };
+
+    one_line(&bodies_best_text(text_bodies, html_bodies))
+        .or_else(from_event)
+        .or_else(from_attachment)
+}
+
+/// The first tag of a Content-Language header such as `en-US, fr` (RFC 3282)
+fn first_language_tag(value: &mail_parser::HeaderValue<'_>) -> Option<String> {
+    value
+        .as_text_list()?
+        .iter()
+        .flat_map(|tags| tags.split(','))
+        .map(str::trim)
+        .find(|tag| !tag.is_empty())
+        .map(ToOwned::to_owned)
+}
+
+/// IMAP-style dotted paths of every part, indexed like `message.parts`. The children
+/// of the top-level multipart are `1`, `2`, ..., and a message that isn't multipart
+/// has its single part as `1`, so the top-level multipart itself has an empty path.
+fn part_ids(message: &mail_parser::Message<'_>) -> Vec<String> {
+    fn visit(message: &mail_parser::Message<'_>, id: usize, path: &str, ids: &mut Vec<String>) {
+        let Some(part) = message.parts.get(id) else {
+            return;
+        };
+        ids[id] = path.to_owned();
+        if let mail_parser::PartType::Multipart(children) = &part.body {
+            for (index, &child) in children.iter().enumerate() {
+                let child_path = if path.is_empty() {
+                    (index + 1).to_string()
+                } else {
+                    format!("{path}.{}", index + 1)
+                };
+                visit(message, child, &child_path, ids);
+            }
+        }
+    }
+
+    let mut ids = vec![String::new(); message.parts.len()];
+    let is_multipart = message
+        .parts
+        .first()
+        .is_some_and(|part| matches!(part.body, mail_parser::PartType::Multipart(_)));
+    visit(message, 0, if is_multipart { "" } else { "1" }, &mut ids);
+    ids
+}
+
+/// Reads a From or To header as both its raw text and its addresses.
+fn parse_address_header(
+    message: &mail_parser::Message<'_>,
+    name: HeaderName<'static>,
+    missing: ParserError,
+) -> Return<(EmailAddressWithText, Vec<EmailAddress>)> {
+    let text = message
+        .header_raw(name.clone())
+        .map(|text| text.trim().to_owned().decode_header());
+    let addresses: Option<Vec<EmailAddress>> = message
+        .header(name)
+        .and_then(|header| header.as_address())
+        .map(parse_address)
+        .filter(|addresses| !addresses.is_empty());
+
+    let (Some(text), Some(addresses)) = (text, addresses) else {
+        return Err(missing);
+    };
+    let Some(first) = addresses
+        .iter()
+        .find(|addr| !addr.is_group)
+        .or(addresses.first())
+    else {
+        return Err(missing);
+    };
+    let address = EmailAddressWithText {
+        name: first.name.to_owned(),
+        text,
+        address: first.address.to_owned(),
+    };
+
+    Ok((address, addresses))
+}
+
+/// The Date header as a Unix epoch in seconds, falling back to the most recent
+/// Received header when it's missing or invalid.
+fn parse_date(message: &mail_parser::Message<'_>) -> Option<i64> {
+    message
+        .date()
+        .filter(|d| d.is_valid())
+        .copied()
+        .or_else(|| message.received().and_then(|r| r.date()))
+        .filter(|d| d.is_valid())
+        .map(|d| d.to_timestamp())
+}
+
+#[derive(uniffi::Record)]
+struct EmailSummary {
+    from: EmailAddressWithText,
+    to: EmailAddressWithText,
+    subject: Option<String>,
+    /// Unix epoch in seconds, as in `Email::date`
+    date: Option<i64>,
+    message_id: Option<String>,
+}
+
+/// Parses only the headers needed to list an email, skipping its bodies and
+/// attachments. Much faster than `parse_email` on large emails.
+#[uniffi::export]
+fn parse_email_headers(raw: String) -> Return<EmailSummary> {
+    let raw = base64_decode(&raw, Base64Variant::UrlSafe)?;
+    let parser = MessageParser::default();
+    let message = parser
+        .parse_headers(raw.as_bytes())
+        .ok_or(ParserError::EmailParseFailed)?;
+
+    let (from, _) = parse_address_header(&message, HeaderName::From, ParserError::NoFromHeader)?;
+    let (to, _) = parse_address_header(&message, HeaderName::To, ParserError::NoToHeader)?;
+
+    Ok(EmailSummary {
+        from,
+        to,
+        subject: message.subject().map(ToOwned::to_owned),
+        date: parse_date(&message),
+        message_id: message.message_id().map(ToOwned::to_owned),
+    })
+}
+
+fn is_calendar_part(part: &mail_parser::MessagePart<'_>) -> bool {
+    part.content_type()
+        .is_some_and(|typ| typ.ctype() == "text" && typ.subtype().is_some_and(|s| s == "calendar"))
+}
+
+#[derive(uniffi::Record)]
+struct Attachment {
+    /// File name from the Content-Disposition or Content-Type header, with RFC 2231
+    /// continuations joined and decoded
+    filename: Option<String>,
+    /// MIME type, e.g. `application/pdf`
+    content_type: Option<String>,
+    /// Size of the decoded contents in bytes
+    size: u64,
+    content_id: Option<String>,
+    /// Whether the part is an image referenced by an HTML body rather than an
+    /// attachment the user would see. Inline parts aren't counted in `attachment_count`.
+    inline: bool,
+    /// Decoded contents of inline parts when `ParseOptions::inline_part_data` is set, so
+    /// images can be shown without parsing the email again. Empty otherwise.
+    #[uniffi(default = [])]
+    data: Vec<u8>,
+    /// Standard base64 of the contents when smaller than
+    /// `ParseOptions::inline_attachments_below_bytes`, so small files like vCards
+    /// don't need a separate fetch. Not set when the contents are already in `data`.
+    #[uniffi(default = None)]
+    content_base64: Option<String>,
+}
+
+/// Parses only the attachments of an email, skipping the body and structured data
+/// processing `parse_email` does. HTML bodies are only searched for content ID
+/// references to tell inline parts apart.
+#[uniffi::export]
+fn parse_attachments_only(raw: String) -> Return<Vec<Attachment>> {
+    let raw = base64_decode(&raw, Base64Variant::UrlSafe)?;
+    let parser = MessageParser::default();
+    let message = parser.parse(&raw).ok_or(ParserError::EmailParseFailed)?;
+
+    Ok(extract_attachments(&message, None, false))
+}
+
+/// Lists the attachments of an email, excluding calendar invites.
+///
+/// Parts with an `attachment` disposition are never inline. Other parts are inline
+/// when their content ID is referenced by an HTML body.
+fn extract_attachments(
+    message: &mail_parser::Message<'_>,
+    inline_below_bytes: Option<u64>,
+    inline_part_data: bool,
+) -> Vec<Attachment> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let html_bodies: Vec<String> = message.html_bodies().map(body_text).collect();
+    let is_referenced = |cid: &str| {
+        let cid = format!("cid:{}", cid.trim_start_matches('<').trim_end_matches('>'));
+        html_bodies.iter().any(|html| html.contains(&cid))
+    };
+
+    message
+        .attachments()
+        .filter(|part| !is_calendar_part(part))
+        .map(|part| {
+            let is_attachment = part
+                .content_disposition()
+                .is_some_and(|disposition| disposition.is_attachment());
+            let content_type = part.content_type().map(|typ| match typ.subtype() {
+                Some(subtype) => format!("{}/{}", typ.ctype(), subtype),
+                None => typ.ctype().to_owned(),
+            });
+
+            let inline = !is_attachment && part.content_id().is_some_and(is_referenced);
+            let with_data = inline && inline_part_data;
+
+            Attachment {
+                filename: part.attachment_name().map(ToOwned::to_owned),
+                content_type,
+                size: part.contents().len() as u64,
+                content_id: part.content_id().map(ToOwned::to_owned),
+                inline,
+                data: if with_data {
+                    part.contents().to_vec()
+                } else {
+                    Vec::new()
+                },
+                content_base64: inline_below_bytes
+                    .filter(|&limit| !with_data && (part.contents().len() as u64) < limit)
+                    .map(|_| STANDARD.encode(part.contents())),
+            }
+        })
+        .collect()
+}
+
+/// Parses an email, keeping only the markups whose top-level `@type` is one of `markup_types`.
+///
+/// Types are compared case-sensitively, following schema.org naming.
+#[uniffi::export]
+fn parse_email_filtered(raw: String, markup_types: Vec<String>) -> Return<Email> {
+    let mut email = parse_email(raw)?;
+    email
+        .markups
+        .retain(|markup| markup_has_type(markup, &markup_types));
+    Ok(email)
+}
+
+fn markup_has_type(markup: &str, types: &[String]) -> bool {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(markup) else {
+        return false;
+    };
+
+    match value.get("@type") {
+        Some(serde_json::Value::String(typ)) => types.contains(typ),
+        Some(serde_json::Value::Array(typs)) => typs
+            .iter()
+            .filter_map(serde_json::Value::as_str)
+            .any(|typ| types.iter().any(|t| t == typ)),
+        _ => false,
+    }
+}
+
+/// Trimmed contents of the JSON-LD scripts, in document order
+fn json_ld_scripts(html: &str) -> Vec<String> {
+    static SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
+        Selector::parse(r#"script[type="application/ld+json"]"#)
+            .expect("failed to create json ld scripts selector")
+    });
+
+    let document = Html::parse_document(html);
+
+    document
+        .select(&SELECTOR)
+        .filter_map(|el| el.text().next())
+        .map(|raw| raw.trim().to_owned())
+        .collect()
+}
+
+/// Whether a JSON-LD `@context` is schema.org, given as its URL, a list holding
+/// it, or an object with it as `@vocab`. The scheme and a trailing slash don't
+/// matter, and neither do the query and fragment.
+fn is_schema_org_context(context: &serde_json::Value) -> bool {
+    match context {
+        serde_json::Value::String(url) => Url::parse(url.trim()).is_ok_and(|url| {
+            matches!(url.scheme(), "http" | "https")
+                && url.host_str() == Some("schema.org")
+                && url.path() == "/"
+        }),
+        serde_json::Value::Array(contexts) => contexts.iter().any(is_schema_org_context),
+        serde_json::Value::Object(context) => {
+            context.get("@vocab").is_some_and(is_schema_org_context)
+        }
+        _ => false,
+    }
+}
+
+fn parse_json_lds(html: &str, schema_org_only: bool) -> Vec<String> {
+    let mut results = Vec::new();
+    let keep = |val: &serde_json::Value| {
+        !schema_org_only || val.get("@context").is_some_and(is_schema_org_context)
+    };
+
+    for script in json_ld_scripts(html) {
+        match serde_json::from_str::<serde_json::Value>(&script) {
+            Ok(serde_json::Value::Array(arr)) => {
+                for val in arr.into_iter().filter(keep) {
+                    if let Ok(s) = serde_json::to_string(&val) {
+                        results.push(s);
+                    }
+                }
+            }
+            Ok(val) if keep(&val) => {
+                if let Ok(s) = serde_json::to_string(&val) {
+                    results.push(s);
+                }
+            }
+            Ok(_) => {}
+            Err(_) => {
+                // Ignore and return nothing for this script
+            }
+        }
+    }
+
+    results
+}
+
+#[derive(serde::Deserialize)]
+#[allow(non_snake_case)]
+struct GmailMessageIn {
+    id: String,
+    threadId: String,
+    labelIds: Vec<String>,
+    snippet: String,
+    sizeEstimate: u32,
+    raw: String,
+    historyId: String,
+    internalDate: String,
+}
+
+#[derive(uniffi::Record)]
+#[allow(non_snake_case)]
+struct GmailMessage {
+    id: String,
+    threadId: String,
+    labelIds: Vec<String>,
+    snippet: EmailText,
+    sizeEstimate: u32,
+    data: Email,
+    historyId: String,
+    internalDate: String,
+}
+
+#[derive(uniffi::Record, serde::Deserialize)]
+struct GmailError {
+    code: u32,
+    message: String,
+    status: String,
+    errors: Vec<GmailErrorItem>,
+}
+
+#[derive(uniffi::Record, serde::Deserialize)]
+struct GmailErrorItem {
+    message: String,
+    domain: String,
+    reason: String,
+}
+
+#[derive(uniffi::Enum)]
+#[allow(clippy::large_enum_variant)]
+enum BatchResponse {
+    Success(GmailMessage),
+    Error(GmailError),
+    /// A message from a provider other than Gmail, which has no Gmail metadata
+    Message(Email),
+}
+
+#[derive(uniffi::Record)]
+struct BatchSection {
+    batch_name: String,
+    response: BatchResponse,
+}
+
+fn parse_gmail(
+    GmailMessageIn {
+        id,
+        threadId,
+        labelIds,
+        snippet,
+        sizeEstimate,
+        raw,
+        historyId,
+        internalDate,
+    }: GmailMessageIn,
+) -> Option<GmailMessage> {
+    Some(GmailMessage {
+        id,
+        threadId,
+        labelIds,
+        snippet: parse_text(snippet),
+        sizeEstimate,
+        historyId,
+        internalDate,
+        data: parse_email(raw).ok()?,
+    })
+}
+
+fn parse_batch_section(section: &str) -> Option<BatchSection> {
+    let batch_name = section.lines().next()?.trim().to_owned();
+    let json_start = section.find('{')?;
+    let json_end = section.rfind('}')?;
+
+    let json = &section[json_start..=json_end];
+
+    let success = serde_json::from_str(json)
+        .ok()
+        .and_then(parse_gmail)
+        .map(BatchResponse::Success);
+    let error = serde_json::from_str(json).ok().map(BatchResponse::Error);
+
+    let response = success.or(error)?;
+
+    Some(BatchSection {
+        batch_name,
+        response,
+    })
+}
+
+#[uniffi::export]
+fn parse_batch_response(body: String) -> Vec<BatchSection> {
+    body.split("--")
+        .par_bridge()
+        .filter_map(parse_batch_section)
+        .collect()
+}
+
+/// Receives the progress of `parse_batch_response_with_progress`.
+#[uniffi::export(callback_interface)]
+pub trait BatchProgress: Send + Sync {
+    /// Called once per section of the batch, from the thread that parsed it, after
+    /// `index + 1` of the `total` sections were parsed. Sections are parsed in
+    /// parallel, so calls can come from several threads at once and may arrive
+    /// slightly out of order.
+    fn on_section(&self, index: u32, total: u32);
+}
+
+/// `parse_batch_response`, reporting progress as sections are parsed. `total`
+/// counts every part of the body between boundaries, including the ones that
+/// turn out not to be responses, so progress always reaches it.
+#[uniffi::export]
+fn parse_batch_response_with_progress(
+    body: String,
+    progress: Box<dyn BatchProgress>,
+) -> Vec<BatchSection> {
+    let sections: Vec<&str> = body.split("--").collect();
+    let total = sections.len() as u32;
+    let parsed = std::sync::atomic::AtomicU32::new(0);
+
+    sections
+        .into_par_iter()
+        .filter_map(|section| {
+            let section = parse_batch_section(section);
+            let index = parsed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            progress.on_section(index, total);
+            section
+        })
+        .collect()
+}
+
+#[derive(serde::Deserialize)]
+struct GraphBatchIn {
+    responses: Vec<GraphResponseIn>,
+}
+
+#[derive(serde::Deserialize)]
+struct GraphResponseIn {
+    id: String,
+    status: u32,
+    #[serde(default)]
+    body: serde_json::Value,
+}
+
+#[derive(serde::Deserialize)]
+struct GraphErrorIn {
+    error: GraphErrorBodyIn,
+}
+
+#[derive(serde::Deserialize)]
+struct GraphErrorBodyIn {
+    code: String,
+    message: String,
+}
+
+/// Parses a Microsoft Graph `$batch` response of `/messages/{id}/$value` requests.
+///
+/// Graph base64 encodes the MIME content of each message, which is parsed as
+/// `parse_email` would. Failed requests are mapped to a `GmailError` whose `code`
+/// is the HTTP status and `status` the Graph error code. `batch_name` is the
+/// request `id`.
+#[uniffi::export]
+fn parse_graph_batch(body: String) -> Vec<BatchSection> {
+    let Ok(batch) = serde_json::from_str::<GraphBatchIn>(&body) else {
+        return Vec::new();
+    };
+
+    batch
+        .responses
+        .into_par_iter()
+        .filter_map(|GraphResponseIn { id, status, body }| {
+            let response = if (200..300).contains(&status) {
+                let raw = body.as_str()?.to_owned();
+                let options = ParseOptions {
+                    base64_variant: Some(Base64Variant::Auto),
+                    ..Default::default()
+                };
+                BatchResponse::Message(parse_email_with_options(raw, options).ok()?)
+            } else {
+                let error = serde_json::from_value::<GraphErrorIn>(body).ok()?.error;
+                BatchResponse::Error(GmailError {
+                    code: status,
+                    message: error.message,
+                    status: error.code,
+                    errors: Vec::new(),
+                })
+            };
+
+            Some(BatchSection {
+                batch_name: id,
+                response,
+            })
+        })
+        .collect()
+}
+
+#[derive(uniffi::Record)]
+struct Thread {
+    thread_id: String,
+    /// Oldest first
+    messages: Vec<GmailMessage>,
+    /// Normalized subject of the oldest message that has one
+    subject: String,
+    /// Unix epoch in seconds of the newest message
+    latest_date: Option<i64>,
+}
+
+impl GmailMessage {
+    /// Unix epoch in seconds, using Gmail's `internalDate` when the email has no date.
+    fn timestamp(&self) -> Option<i64> {
+        self.data.date.or_else(|| {
+            self.internalDate
+                .parse::<i64>()
+                .ok()
+                .map(|millis| millis / 1000)
+        })
+    }
+
+    /// Message IDs from References and In-Reply-To, from the thread root down.
+    fn ancestor_ids(&self) -> Vec<String> {
+        static MESSAGE_ID_RE: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"<([^>]+)>").expect("expression is valid"));
+
+        let header = |name: &str| {
+            self.data
+                .headers
+                .iter()
+                .filter(|h| h.name.eq_ignore_ascii_case(name))
+                .flat_map(|h| MESSAGE_ID_RE.captures_iter(&h.value))
+                .map(|captures| captures[1].trim().to_owned())
+                .collect::<Vec<String>>()
+        };
+
+        let mut ids = header("References");
+        ids.extend(header("In-Reply-To"));
+        ids
+    }
+}
+
+/// Groups messages into conversations by Gmail `threadId`. Messages without one
+/// join the thread of a message they reference through References or In-Reply-To,
+/// or start a thread keyed by their root message ID.
+///
+/// Threads are returned newest first, with their messages oldest first.
+#[uniffi::export]
+fn group_threads(mut messages: Vec<GmailMessage>) -> Vec<Thread> {
+    messages.sort_by_key(GmailMessage::timestamp);
+
+    let mut thread_ids: HashMap<String, String> = HashMap::new();
+    for message in messages.iter().filter(|m| !m.threadId.is_empty()) {
+        if let Some(message_id) = &message.data.message_id {
+            thread_ids.insert(message_id.to_owned(), message.threadId.to_owned());
+        }
+    }
+
+    let mut threads: Vec<Thread> = Vec::new();
+    for message in messages {
+        let thread_id = if !message.threadId.is_empty() {
+            message.threadId.to_owned()
+        } else {
+            let ancestors = message.ancestor_ids();
+            let thread_id = ancestors
+                .iter()
+                .find_map(|id| thread_ids.get(id).cloned())
+                .or_else(|| ancestors.first().cloned())
+                .or_else(|| message.data.message_id.clone())
+                .unwrap_or_else(|| message.id.to_owned());
+            if let Some(message_id) = &message.data.message_id {
+                thread_ids.insert(message_id.to_owned(), thread_id.to_owned());
+            }
+            thread_id
+        };
+
+        let thread = match threads.iter_mut().position(|t| t.thread_id == thread_id) {
+            Some(index) => &mut threads[index],
+            None => {
+                threads.push(Thread {
+                    thread_id,
+                    messages: Vec::new(),
+                    subject: String::new(),
+                    latest_date: None,
+                });
+                threads.last_mut().expect("thread was just pushed")
+            }
+        };
+
+        if thread.subject.is_empty() {
+            if let Some(subject) = &message.data.subject_normalized {
+                thread.subject = subject.to_owned();
+            }
+        }
+        thread.latest_date = thread.latest_date.max(message.timestamp());
+        thread.messages.push(message);
+    }
+
+    threads.sort_by_key(|t| std::cmp::Reverse(t.latest_date));
+    threads
+}
+
+/// Computes a hex-encoded BLAKE3 hash of an email's meaningful content, so the same
+/// message fetched twice hashes identically.
+///
+/// The hash covers, in this order:
+/// - the normalized subject (see `normalize_subject`)
+/// - the lowercased From address
+/// - the lowercased To and Cc addresses, sorted
+/// - the Date timestamp
+/// - every text body's `text`, then every HTML body's `text`, with tracking pixel URLs
+///   removed
+///
+/// Everything else, including the other headers and their order, is ignored.
+#[uniffi::export]
+fn content_hash(email: &Email) -> String {
+    let mut hasher = blake3::Hasher::new();
+    let mut field = |value: &str| {
+        hasher.update(value.as_bytes());
+        hasher.update(&[0]);
+    };
+
+    field(email.subject_normalized.as_deref().unwrap_or_default());
+    field(&email.from.address.to_lowercase());
+
+    let mut recipients: Vec<String> = email
+        .to_addresses
+        .iter()
+        .chain(&email.cc_addresses)
+        .map(|addr| addr.address.to_lowercase())
+        .collect();
+    recipients.sort();
+    recipients.iter().for_each(|addr| field(addr));
+
+    field(&email.date.map(|d| d.to_string()).unwrap_or_default());
+
+    // HTML-only emails have their HTML body mirrored into `text_bodies`
+    for body in email.text_bodies.iter().chain(&email.html_bodies) {
+        let text = detect_tracking_pixels(&body.text)
+            .iter()
+            .fold(body.text.clone(), |text, src| text.replace(src, ""));
+        field(&text);
+    }
+
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Text bodies that are actual `text/plain` parts. HTML-only emails have their HTML
+/// part mirrored into `text_bodies`, and those copies are skipped.
+fn plain_text_bodies(email: &Email) -> impl DoubleEndedIterator<Item = &EmailText> {
+    email
+        .text_bodies
+        .iter()
+        .filter(|body| body.content_type != "text/html")
+}
+
+/// Picks the HTML body to display: the one with the most text once tags are stripped,
+/// which is the richest alternative when there are several. Ties go to the first body.
+///
+/// Returns `None` when there are no HTML bodies; use `primary_text_body` then.
+#[uniffi::export]
+fn primary_html_body(email: &Email) -> Option<EmailText> {
+    email
+        .html_bodies
+        .iter()
+        .map(|body| (html_to_text(&body.text).len(), body))
+        .filter(|(len, _)| *len > 0)
+        .rev()
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, body)| body.clone())
+}
+
+/// Picks the plain text body to display: the longest `text/plain` part.
+/// Ties go to the first body.
+#[uniffi::export]
+fn primary_text_body(email: &Email) -> Option<EmailText> {
+    plain_text_bodies(email)
+        .filter(|body| !body.text.trim().is_empty())
+        .rev()
+        .max_by_key(|body| body.text.trim().len())
+        .cloned()
+}
+
+#[uniffi::export]
+fn escape_text(text: String) -> String {
+    html_escape::encode_text(&text).into_owned()
+}
+
+#[derive(uniffi::Record)]
+struct CalendarEvent {
+    uid: Option<String>,
+    summary: Option<String>,
+    status: Option<CalendarEventStatus>,
+    url: Option<String>,
+    google_conference_link: Option<String>,
+    /// Join URL of a Zoom, Teams, Webex or Google Meet meeting, found in the vendor
+    /// `X-` properties, LOCATION, DESCRIPTION or URL (see `find_conference_link`)
+    conference_link: Option<String>,
+    location: Option<String>,
+    /// WGS 84 latitude of the location in degrees, from GEO or the markup's `geo`
+    latitude: Option<f64>,
+    /// WGS 84 longitude of the location in degrees, from GEO or the markup's `geo`
+    longitude: Option<f64>,
+    timestamp: Option<i64>,
+    last_modified: Option<i64>,
+    created: Option<i64>,
+    start: Option<i64>,
+    end: Option<i64>,
+    /// ISO-8601 start time in the event's own wall-clock time (see `get_iso_string`)
+    start_iso: Option<String>,
+    /// ISO-8601 end time in the event's own wall-clock time (see `get_iso_string`).
+    /// `None` for events with a DURATION instead of DTEND, whose `end` is derived.
+    end_iso: Option<String>,
+    /// Whether the event blocks time, from TRANSP. `None` when not given,
+    /// which RFC 5545 treats as `Opaque`.
+    transparency: Option<Transparency>,
+    /// Revision of the event, incremented by the organizer on each significant update
+    sequence: Option<u32>,
+    /// Unix epoch in milliseconds of the recurring event instance this one replaces
+    recurrence_id: Option<i64>,
+    /// Whether the event should be removed from the calendar, either because the
+    /// invite is an iTIP `METHOD:CANCEL` or because its status is cancelled
+    is_cancellation: bool,
+}
+
+#[derive(uniffi::Enum, Debug, PartialEq)]
+enum Transparency {
+    /// Blocks time on busy time searches.
+    Opaque,
+    /// Does not block time on busy time searches.
+    Transparent,
+}
+
+#[derive(uniffi::Enum)]
+enum CalendarEventStatus {
+    /// Indicates event is tentative.
+    Tentative,
+    /// Indicates event is definite.
+    Confirmed,
+    /// Indicates event was cancelled.
+    Cancelled,
+}
+
+impl From<icalendar::EventStatus> for CalendarEventStatus {
+    fn from(value: icalendar::EventStatus) -> Self {
+        match value {
+            icalendar::EventStatus::Tentative => Self::Tentative,
+            icalendar::EventStatus::Confirmed => Self::Confirmed,
+            icalendar::EventStatus::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+fn parse_events(body: &str) -> Option<Vec<CalendarEvent>> {
+    let calendar: Calendar = body.parse().ok()?;
+    let is_cancel_method = calendar
+        .property_value("METHOD")
+        .is_some_and(|method| method.trim().eq_ignore_ascii_case("CANCEL"));
+
+    Some(
+        calendar
+            .components
+            .into_iter()
+            .filter_map(parse_calendar_event)
+            .map(|mut event| {
+                event.is_cancellation |= is_cancel_method;
+                event
+            })
+            .collect(),
+    )
+}
+
+/// The first meeting join URL in `texts`, for Zoom, Microsoft Teams, Webex or Google Meet.
+fn find_conference_link<'a>(texts: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    static CONFERENCE_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r#"(?i)https://(?:[\w-]+\.)*(?:zoom\.us/(?:j|my|w|s)/|teams\.microsoft\.com/l/meetup-join/|teams\.live\.com/meet/|webex\.com/|meet\.google\.com/[a-z])[^\s<>"'\\]*"#,
+        )
+        .expect("expression is valid")
+    });
+
+    texts
+        .into_iter()
+        .find_map(|text| CONFERENCE_RE.find(text))
+        .map(|found| {
+            found
+                .as_str()
+                .trim_end_matches(['.', ',', ')', ';'])
+                .to_owned()
+        })
+}
+
+/// A latitude and longitude pair, if both are in range.
+fn geo_coordinates(latitude: f64, longitude: f64) -> Option<(f64, f64)> {
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+        .then_some((latitude, longitude))
+}
+
+/// Reads a GEO property such as `37.386013;-122.082932`.
+fn parse_ics_geo(value: &str) -> Option<(f64, f64)> {
+    let (latitude, longitude) = value.split_once(';')?;
+    geo_coordinates(
+        latitude.trim().parse().ok()?,
+        longitude.trim().parse().ok()?,
+    )
+}
+
+/// Reads an RFC 5545 DURATION such as `PT1H30M` or `-P1W` into milliseconds. Days
+/// and weeks are counted as 24 hours.
+fn parse_ics_duration(value: &str) -> Option<i64> {
+    let value = value.trim().to_ascii_uppercase();
+    let (sign, value) = match value.as_bytes().first()? {
+        b'-' => (-1, &value[1..]),
+        b'+' => (1, &value[1..]),
+        _ => (1, &value[..]),
+    };
+    let value = value.strip_prefix('P')?;
+
+    let mut millis: i64 = 0;
+    let mut number: Option<i64> = None;
+    let mut in_time = false;
+    let mut has_component = false;
+    for c in value.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            number = Some(
+                number
+                    .unwrap_or(0)
+                    .checked_mul(10)?
+                    .checked_add(digit.into())?,
+            );
+            continue;
+        }
+        if c == 'T' && !in_time && number.is_none() {
+            in_time = true;
+            continue;
+        }
+
+        let unit_seconds = match (c, in_time) {
+            ('W', false) => 7 * 24 * 3600,
+            ('D', false) => 24 * 3600,
+            ('H', true) => 3600,
+            ('M', true) => 60,
+            ('S', true) => 1,
+            _ => return None,
+        };
+        millis = millis.checked_add(number.take()?.checked_mul(unit_seconds * 1000)?)?;
+        has_component = true;
+    }
+
+    (has_component && number.is_none()).then_some(sign * millis)
+}
+
+fn parse_calendar_event(comp: icalendar::CalendarComponent) -> Option<CalendarEvent> {
+    let event = comp.as_event()?;
+
+    // Uses LAST_MODIFIED instead
+    let last_modified1 = event.get_last_modified().map(|x| x.timestamp_millis());
+    let last_modified2 = event
+        .properties()
+        .get("LAST-MODIFIED")
+        .and_then(DatePerhapsTime::from_property)
+        .and_then(get_timestamp);
+
+    let last_modified = last_modified1.or(last_modified2);
+
+    let google_conference_link = event
+        .property_value("X-GOOGLE-CONFERENCE")
+        .map(|x| x.to_owned());
+    let mut vendor_properties: Vec<(&String, &str)> = event
+        .properties()
+        .iter()
+        .filter(|(name, _)| name.starts_with("X-"))
+        .map(|(name, property)| (name, property.value()))
+        .collect();
+    vendor_properties.sort();
+    let conference_link = find_conference_link(
+        vendor_properties
+            .into_iter()
+            .map(|(_, value)| value)
+            .chain(event.get_location())
+            .chain(event.get_description())
+            .chain(event.get_url()),
+    )
+    .or_else(|| google_conference_link.clone());
+    let geo = event.property_value("GEO").and_then(parse_ics_geo);
+    let start = event.get_start().and_then(get_timestamp);
+    // Events may give their length in DURATION instead of DTEND
+    let end = event.get_end().and_then(get_timestamp).or_else(|| {
+        let duration = event
+            .property_value("DURATION")
+            .and_then(parse_ics_duration)?;
+        start?.checked_add(duration)
+    });
+
+    Some(CalendarEvent {
+        uid: event.get_uid().map(|s| s.to_owned()),
+        summary: event.get_summary().map(|s| s.to_owned()),
+        status: event.get_status().map(|s| s.into()),
+        url: event.get_url().map(|x| x.to_owned()),
+        google_conference_link,
+        conference_link,
+        location: event.get_location().map(|x| x.to_string()),
+        latitude: geo.map(|(latitude, _)| latitude),
+        longitude: geo.map(|(_, longitude)| longitude),
+        timestamp: event.get_timestamp().map(|x| x.timestamp_millis()),
+        last_modified,
+        created: event.get_created().map(|x| x.timestamp_millis()),
+        start,
+        end,
+        start_iso: event.get_start().map(get_iso_string),
+        end_iso: event.get_end().map(get_iso_string),
+        transparency: event.property_value("TRANSP").and_then(|x| {
+            match x.trim().to_ascii_uppercase().as_str() {
+                "OPAQUE" => Some(Transparency::Opaque),
+                "TRANSPARENT" => Some(Transparency::Transparent),
+                _ => None,
+            }
+        }),
+        sequence: event
+            .property_value("SEQUENCE")
+            .and_then(|x| x.trim().parse().ok()),
+        recurrence_id: event
+            .properties()
+            .get("RECURRENCE-ID")
+            .and_then(DatePerhapsTime::from_property)
+            .and_then(get_timestamp),
+        is_cancellation: matches!(event.get_status(), Some(icalendar::EventStatus::Cancelled)),
+    })
+}
+
+/// Formats a calendar time as ISO-8601 without normalizing it to UTC.
+///
+/// - UTC times are suffixed with `Z`
+/// - Times with a known TZID carry that zone's offset, e.g. `2025-01-01T09:00:00+05:30`
+/// - Floating times and times with an unknown TZID have no offset
+/// - All-day events are formatted as a plain date, e.g. `2025-01-01`
+fn get_iso_string(x: icalendar::DatePerhapsTime) -> String {
+    const DATE_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+    match x {
+        DatePerhapsTime::DateTime(calendar_date_time) => match calendar_date_time {
+            icalendar::CalendarDateTime::Floating(naive_date_time) => {
+                naive_date_time.format(DATE_TIME_FORMAT).to_string()
+            }
+            icalendar::CalendarDateTime::Utc(date_time) => {
+                date_time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+            }
+            icalendar::CalendarDateTime::WithTimezone { date_time, tzid } => tzid
+                .parse::<chrono_tz::Tz>()
+                .ok()
+                .and_then(|tz| tz.from_local_datetime(&date_time).single())
+                .map(|local_dt| local_dt.format("%Y-%m-%dT%H:%M:%S%:z").to_string())
+                .unwrap_or_else(|| date_time.format(DATE_TIME_FORMAT).to_string()),
+        },
+        DatePerhapsTime::Date(naive_date) => naive_date.format("%Y-%m-%d").to_string(),
+    }
+}
+
+fn is_schema_type(typ: &str, expected: &str) -> bool {
+    let typ = typ.trim_end_matches('/');
+    typ == expected || typ.ends_with(&format!("/{expected}"))
+}
+
+fn markup_str<'a>(value: &'a serde_json::Value, name: &str) -> Option<&'a str> {
+    value
+        .get(name)
+        .and_then(serde_json::Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
+
+/// Converts a schema.org `Event` markup, or the event an `EventReservation` is for,
+/// into a calendar event.
+fn markup_calendar_event(value: &serde_json::Value) -> Option<CalendarEvent> {
+    let typ = markup_str(value, "@type")?;
+    if is_schema_type(typ, "EventReservation") {
+        return markup_calendar_event(value.get("reservationFor")?);
+    }
+    if !typ.ends_with("Event") {
+        return None;
+    }
+
+    let location = match value.get("location") {
+        Some(serde_json::Value::String(location)) => Some(location.trim().to_owned()),
+        Some(place @ serde_json::Value::Object(_)) => {
+            let address = match place.get("address") {
+                Some(serde_json::Value::String(address)) => Some(address.trim().to_owned()),
+                Some(address @ serde_json::Value::Object(_)) => {
+                    let parts: Vec<&str> = [
+                        "streetAddress",
+                        "addressLocality",
+                        "addressRegion",
+                        "postalCode",
+                        "addressCountry",
+                    ]
+                    .iter()
+                    .filter_map(|name| markup_str(address, name))
+                    .collect();
+                    Some(parts.join(", ")).filter(|a| !a.is_empty())
+                }
+                _ => None,
+            };
+            match (markup_str(place, "name"), address) {
+                (Some(name), Some(address)) => Some(format!("{name}, {address}")),
+                (Some(name), None) => Some(name.to_owned()),
+                (None, address) => address,
+            }
+        }
+        _ => None,
+    };
+
+    let geo = value
+        .get("location")
+        .and_then(|place| place.get("geo"))
+        .and_then(|geo| {
+            let coordinate = |name: &str| match geo.get(name)? {
+                serde_json::Value::Number(number) => number.as_f64(),
+                serde_json::Value::String(text) => text.trim().parse().ok(),
+                _ => None,
+            };
+            geo_coordinates(coordinate("latitude")?, coordinate("longitude")?)
+        });
+
+    let status = markup_str(value, "eventStatus").and_then(|status| {
+        if is_schema_type(status, "EventCancelled") {
+            Some(CalendarEventStatus::Cancelled)
+        } else if is_schema_type(status, "EventScheduled") {
+            Some(CalendarEventStatus::Confirmed)
+        } else {
+            None
+        }
+    });
+    let is_cancellation = matches!(status, Some(CalendarEventStatus::Cancelled));
+
+    Some(CalendarEvent {
+        uid: markup_str(value, "@id").map(ToOwned::to_owned),
+        summary: markup_str(value, "name").map(ToOwned::to_owned),
+        status,
+        url: markup_str(value, "url").map(ToOwned::to_owned),
+        google_conference_link: None,
+        conference_link: find_conference_link(
+            location
+                .as_deref()
+                .into_iter()
+                .chain(markup_str(value, "url")),
+        ),
+        location,
+        latitude: geo.map(|(latitude, _)| latitude),
+        longitude: geo.map(|(_, longitude)| longitude),
+        timestamp: None,
+        last_modified: None,
+        created: None,
+        start: markup_str(value, "startDate").and_then(parse_schema_date),
+        end: markup_str(value, "endDate").and_then(parse_schema_date),
+        start_iso: markup_str(value, "startDate").map(ToOwned::to_owned),
+        end_iso: markup_str(value, "endDate").map(ToOwned::to_owned),
+        transparency: None,
+        sequence: None,
+        recurrence_id: None,
+        is_cancellation,
+    })
+}
+
+/// Converts a schema.org `Event` microdata item into a calendar event.
+fn microdata_calendar_event(item: &MicrodataItem) -> Option<CalendarEvent> {
+    if !item.itemtype.as_deref()?.ends_with("Event") {
+        return None;
+    }
+
+    let property = |name: &str| {
+        item.properties
+            .get(name)
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+    };
+
+    let location = item
+        .children
+        .get("location")
+        .map(|place| {
+            let name = place.properties.get("name").map(|s| s.trim());
+            let address = place
+                .properties
+                .get("address")
+                .map(|s| s.trim().to_owned())
+                .or_else(|| {
+                    place.children.get("address").map(|address| {
+                        [
+                            "streetAddress",
+                            "addressLocality",
+                            "addressRegion",
+                            "postalCode",
+                            "addressCountry",
+                        ]
+                        .iter()
+                        .filter_map(|name| address.properties.get(*name))
+                        .map(|s| s.trim())
+                        .filter(|s| !s.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                    })
+                })
+                .filter(|a| !a.is_empty());
+            match (name, address) {
+                (Some(name), Some(address)) => format!("{name}, {address}"),
+                (Some(name), None) => name.to_owned(),
+                (None, address) => address.unwrap_or_default(),
+            }
+        })
+        .or_else(|| property("location").map(ToOwned::to_owned))
+        .filter(|l| !l.is_empty());
+    let geo = item
+        .children
+        .get("location")
+        .and_then(|place| place.children.get("geo"))
+        .and_then(|geo| {
+            let coordinate = |name: &str| geo.properties.get(name)?.trim().parse().ok();
+            geo_coordinates(coordinate("latitude")?, coordinate("longitude")?)
+        });
+
+    Some(CalendarEvent {
+        uid: None,
+        summary: property("name").map(ToOwned::to_owned),
+        status: None,
+        url: property("url").map(ToOwned::to_owned),
+        google_conference_link: None,
+        conference_link: find_conference_link(
+            location.as_deref().into_iter().chain(property("url")),
+        ),
+        location,
+        latitude: geo.map(|(latitude, _)| latitude),
+        longitude: geo.map(|(_, longitude)| longitude),
+        timestamp: None,
+        last_modified: None,
+        created: None,
+        start: property("startDate").and_then(parse_schema_date),
+        end: property("endDate").and_then(parse_schema_date),
+        start_iso: property("startDate").map(ToOwned::to_owned),
+        end_iso: property("endDate").map(ToOwned::to_owned),
+        transparency: None,
+        sequence: None,
+        recurrence_id: None,
+        is_cancellation: false,
+    })
+}
+
+fn get_timestamp(x: icalendar::DatePerhapsTime) -> Option<i64> {
+    match x {
+        DatePerhapsTime::DateTime(calendar_date_time) => match calendar_date_time {
+            icalendar::CalendarDateTime::Floating(naive_date_time) => {
+                let timestamp = naive_date_time.and_utc().timestamp_millis();
+                Some(timestamp)
+            }
+            icalendar::CalendarDateTime::Utc(date_time) => {
+                let timestamp = date_time.timestamp_millis();
+                Some(timestamp)
+            }
+            icalendar::CalendarDateTime::WithTimezone { date_time, tzid } => {
+                let tz: chrono_tz::Tz = tzid.parse().ok()?;
+                let local_dt = tz.from_local_datetime(&date_time).single()?;
+                let timestamp = local_dt.timestamp_millis();
+                Some(timestamp)
+            }
+        },
+        DatePerhapsTime::Date(naive_date) => {
+            let datetime = naive_date.and_hms_opt(0, 0, 0)?;
+            let timestamp = Utc.from_utc_datetime(&datetime).timestamp_millis();
+            Some(timestamp)
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+struct MicrodataItem {
+    itemtype: Option<String>,
+    properties: HashMap<String, String>,
+    children: HashMap<String, MicrodataItem>,
+}
+
+fn extract_microdata_items(element: &scraper::ElementRef) -> MicrodataItem {
+    static PROP_SELECTOR: LazyLock<Selector> =
+        LazyLock::new(|| Selector::parse("[itemprop]").unwrap());
+
+    let itemtype = element.value().attr("itemtype").map(String::from);
+    let mut properties = HashMap::new();
+    let mut children = HashMap::new();
+
+    for prop in element.select(&PROP_SELECTOR) {
+        if prop.value().attr("itemscope").is_some() {
+            if let Some(prop_name) = prop.value().attr("itemprop") {
+                let child = extract_microdata_items(&prop);
+                children.insert(prop_name.to_owned(), child);
+            }
+        } else if let Some(prop_name) = prop.value().attr("itemprop") {
+            let value = normalize_whitespace(&prop.text().collect::<Vec<_>>().join(" "));
+            let value = prop
+                .attr("content")
+                .or(prop.attr("href"))
+                .map(|c| c.to_owned())
+                .unwrap_or(value);
+
+            properties.insert(prop_name.to_owned(), value);
+        }
+    }
+
+    MicrodataItem {
+        itemtype,
+        properties,
+        children,
+    }
+}
+
+fn extract_microdata(html: &str) -> Vec<MicrodataItem> {
+    static ROOT_SELECTOR: LazyLock<Selector> =
+        LazyLock::new(|| Selector::parse("[itemscope]").unwrap());
+
+    let document = Html::parse_document(html);
+
+    document
+        .select(&ROOT_SELECTOR)
+        // Skip nested scopes; only process top-level items here
+        .filter(|el| {
+            el.ancestors()
+                .filter_map(scraper::ElementRef::wrap)
+                .all(|e| e.value().attr("itemscope").is_none())
+        })
+        .map(|el| extract_microdata_items(&el))
+        .collect()
+}
+
+#[derive(uniffi::Record)]
+struct EmailImage {
+    src: String,
+    /// Set for `cid:` images, which have to be resolved against the email's attachments
+    content_id: Option<String>,
+    alt: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+/// Returns the decoded bytes of the inline image most likely to be the sender's logo:
+/// the largest one, with images near the top of the body preferred. Tracking pixels
+/// and spacers under 16px are skipped.
+///
+/// Sizes are compared by displayed area, among the images whose width and height the
+/// HTML gives, and by byte size only when none of them has both. The email must be parsed with `ParseOptions::inline_part_data`, as the
+/// bytes are read from `Attachment::data`.
+#[uniffi::export]
+fn extract_brand_image(email: &Email) -> Option<Vec<u8>> {
+    const MIN_DIMENSION: u32 = 16;
+
+    let normalize = |cid: &str| {
+        cid.trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .to_owned()
+    };
+
+    let candidates: Vec<(usize, Option<f64>, &Attachment)> = email
+        .images
+        .iter()
+        .filter(|image| {
+            let is_spacer = |dimension: Option<u32>| dimension.is_some_and(|d| d < MIN_DIMENSION);
+            !is_spacer(image.width) && !is_spacer(image.height)
+        })
+        .enumerate()
+        .filter_map(|(index, image)| {
+            let content_id = normalize(image.content_id.as_deref()?);
+            let attachment = email.attachments.iter().find(|a| {
+                a.inline
+                    && !a.data.is_empty()
+                    && a.content_type
+                        .as_deref()
+                        .is_some_and(|t| t.starts_with("image/"))
+                    && a.content_id.as_deref().map(normalize).as_deref() == Some(&content_id)
+            })?;
+
+            let area = image
+                .width
+                .zip(image.height)
+                .map(|(width, height)| width as f64 * height as f64);
+            Some((index, area, attachment))
+        })
+        .collect();
+
+    // Areas and byte sizes aren't comparable, so byte sizes are only a fallback
+    let any_area = candidates.iter().any(|(_, area, _)| area.is_some());
+    candidates
+        .into_iter()
+        .filter_map(|(index, area, attachment)| {
+            let size = if any_area {
+                area?
+            } else {
+                attachment.data.len() as f64
+            };
+            // Each image further down the body needs to be larger to win
+            Some((size / (1.0 + index as f64 * 0.5), attachment))
+        })
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, attachment)| attachment.data.clone())
+}
+
+/// Returns the "view in browser" link of a newsletter: the first link whose text, or
+/// its parent's text, reads like one of `WEB_VERSION_KEYWORDS`.
+#[uniffi::export]
+fn extract_web_version_url(email: &Email) -> Option<String> {
+    static ANCHOR_SELECTOR: LazyLock<Selector> =
+        LazyLock::new(|| Selector::parse("a[href]").unwrap());
+
+    const WEB_VERSION_KEYWORDS: &[&str] = &[
+        "view in browser",
+        "view in your browser",
+        "view it in your browser",
+        "view this email in your browser",
+        "open in browser",
+        "view online",
+        "read online",
+        "view as a web page",
+        "view as webpage",
+        "web version",
+        "online version",
+        // French
+        "voir dans le navigateur",
+        "afficher dans le navigateur",
+        "version en ligne",
+        "voir la version web",
+        // German
+        "im browser ansehen",
+        "im browser anzeigen",
+        "online ansehen",
+        "online-version",
+        "webversion",
+        // Spanish
+        "ver en el navegador",
+        "ver en línea",
+        "versión web",
+        // Italian
+        "visualizza nel browser",
+        "versione online",
+        "versione web",
+        // Portuguese
+        "ver no navegador",
+        "versão online",
+        "versão web",
+        // Dutch
+        "bekijk in je browser",
+        "bekijk in uw browser",
+        "online bekijken",
+        // Japanese
+        "ブラウザで表示",
+        "ウェブで見る",
+    ];
+
+    let matches_keyword = |text: &str| {
+        let text = text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        WEB_VERSION_KEYWORDS
+            .iter()
+            .any(|keyword| text.contains(keyword))
+    };
+
+    email.html_bodies.iter().find_map(|body| {
+        let document = Html::parse_document(&body.text);
+        document
+            .select(&ANCHOR_SELECTOR)
+            .filter(|el| {
+                matches_keyword(&el.text().collect::<String>())
+                    || el
+                        .parent()
+                        .and_then(scraper::ElementRef::wrap)
+                        .is_some_and(|parent| matches_keyword(&parent.text().collect::<String>()))
+            })
+            .filter_map(|el| el.attr("href").map(str::trim))
+            .find(|href| href.starts_with("http://") || href.starts_with("https://"))
+            .map(ToOwned::to_owned)
+    })
+}
+
+/// A call to action of an email, as the text and target of its link.
+#[derive(uniffi::Record, Debug, PartialEq)]
+struct Cta {
+    text: String,
+    href: String,
+}
+
+/// Value of a property in an element's inline style, lowercased and without `!important`
+fn style_property(el: &scraper::ElementRef, name: &str) -> Option<String> {
+    el.attr("style")?.split(';').find_map(|declaration| {
+        let (property, value) = declaration.split_once(':')?;
+        let value = value.trim().trim_end_matches("!important").trim();
+        property
+            .trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.to_lowercase())
+    })
+}
+
+/// Finds the main call to action of an HTML body, like "Confirm your order", as the
+/// first link styled as a button. Links count as buttons from their inline styles and
+/// those of their table cell, as email buttons are often a link in a colored cell:
+/// a background that isn't white or transparent, padding, rounded corners, centering,
+/// and a `button`/`btn` class.
+#[uniffi::export]
+fn extract_primary_cta(html: &str) -> Option<Cta> {
+    static ANCHOR_SELECTOR: LazyLock<Selector> =
+        LazyLock::new(|| Selector::parse("a[href]").unwrap());
+
+    const MIN_SCORE: u32 = 4;
+    const EXCLUDED_TEXTS: &[&str] = &["unsubscribe", "view in browser", "view online", "privacy"];
+
+    let has_background = |el: &scraper::ElementRef| {
+        let color = style_property(el, "background-color")
+            .or_else(|| style_property(el, "background"))
+            .or_else(|| el.attr("bgcolor").map(|color| color.trim().to_lowercase()));
+        color.is_some_and(|color| {
+            !matches!(
+                color.as_str(),
+                "" | "none"
+                    | "transparent"
+                    | "inherit"
+                    | "initial"
+                    | "white"
+                    | "#fff"
+                    | "#ffffff"
+                    | "rgb(255,255,255)"
+                    | "rgb(255, 255, 255)"
+            )
+        })
+    };
+    let score = |el: &scraper::ElementRef| {
+        let cell = el
+            .ancestors()
+            .filter_map(scraper::ElementRef::wrap)
+            .take(3)
+            .find(|ancestor| ancestor.value().name() == "td");
+        let styled = |check: &dyn Fn(&scraper::ElementRef) -> bool| {
+            check(el) || cell.as_ref().is_some_and(check)
+        };
+
+        let class = el.attr("class").unwrap_or_default().to_lowercase();
+        let is_centered = |el: &scraper::ElementRef| {
+            style_property(el, "text-align").as_deref() == Some("center")
+                || el
+                    .attr("align")
+                    .is_some_and(|align| align.eq_ignore_ascii_case("center"))
+        };
+
+        [
+            (styled(&has_background), 3),
+            (styled(&|el| style_property(el, "padding").is_some()), 1),
+            (
+                styled(&|el| style_property(el, "border-radius").is_some()),
+                1,
+            ),
+            (styled(&is_centered), 1),
+            (class.contains("button") || class.contains("btn"), 3),
+            (el.attr("role") == Some("button"), 3),
+        ]
+        .iter()
+        .filter(|(matched, _)| *matched)
+        .map(|(_, points)| points)
+        .sum::<u32>()
+    };
+
+    let document = Html::parse_document(html);
+
+    document.select(&ANCHOR_SELECTOR).find_map(|el| {
+        let href = el.attr("href")?.trim();
+        if !(href.starts_with("http://") || href.starts_with("https://")) || is_hidden(&el) {
+            return None;
+        }
+
+        let text = normalize_whitespace(&el.text().collect::<String>()).replace('\n', " ");
+        let lowercase = text.to_lowercase();
+        let is_label = (2..=40).contains(&text.chars().count())
+            && !EXCLUDED_TEXTS
+                .iter()
+                .any(|excluded| lowercase.contains(excluded));
+
+        (is_label && score(&el) >= MIN_SCORE).then(|| Cta {
+            text,
+            href: href.to_owned(),
+        })
+    })
+}
+
+/// Reads a pixel dimension from an image's inline style, falling back to its attribute.
+fn image_dimension(el: &scraper::ElementRef, name: &str) -> Option<u32> {
+    let from_style = el.attr("style").and_then(|style| {
+        style.split(';').find_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            if !property.trim().eq_ignore_ascii_case(name) {
+                return None;
+            }
+            let value = value.trim().trim_end_matches("!important").trim();
+            value
+                .strip_suffix("px")
+                .unwrap_or(value)
+                .trim()
+                .parse()
+                .ok()
+        })
+    });
+
+    from_style.or_else(|| {
+        el.attr(name).and_then(|value| {
+            let value = value.trim();
+            value.strip_suffix("px").unwrap_or(value).parse().ok()
+        })
+    })
+}
+
+fn is_hidden(el: &scraper::ElementRef) -> bool {
+    el.attr("style").is_some_and(|style| {
+        let style = style.replace(' ', "").to_lowercase();
+        style.contains("display:none") || style.contains("visibility:hidden")
+    })
+}
+
+/// Images at most this many pixels wide or high are considered tracking pixels.
+const TRACKING_PIXEL_MAX_DIMENSION: u32 = 2;
+
+/// `Email::alt_text_summary` is only set for HTML bodies with less visible text
+const ALT_TEXT_MAX_VISIBLE_CHARS: usize = 80;
+
+/// Joins the alt texts of an HTML body's images in document order, one per line.
+/// Hidden images, tracking pixels, and decorative alts like `image` or a file
+/// name are skipped, as are repeats of the previous alt.
+#[uniffi::export]
+fn extract_alt_text(html: &str) -> Option<String> {
+    static SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("img[alt]").unwrap());
+
+    const DECORATIVE_ALTS: &[&str] = &[
+        "image", "img", "photo", "picture", "logo", "banner", "spacer", "pixel", "icon",
+    ];
+
+    let document = Html::parse_document(html);
+
+    let mut alts: Vec<String> = Vec::new();
+    for el in document.select(&SELECTOR) {
+        if is_hidden(&el) || is_tracking_pixel(&el, TRACKING_PIXEL_MAX_DIMENSION) {
+            continue;
+        }
+
+        let alt = el.attr("alt").unwrap_or_default();
+        let alt = alt.split_whitespace().collect::<Vec<_>>().join(" ");
+        let lowercase = alt.to_lowercase();
+        let is_file_name = [".png", ".jpg", ".jpeg", ".gif", ".webp", ".svg"]
+            .iter()
+            .any(|ext| lowercase.ends_with(ext));
+        let is_decorative = alt.chars().all(|c| !c.is_alphanumeric())
+            || DECORATIVE_ALTS.contains(&lowercase.as_str())
+            || is_file_name;
+
+        if !is_decorative && alts.last() != Some(&alt) {
+            alts.push(alt);
+        }
+    }
+
+    Some(alts.join("\n")).filter(|text| !text.is_empty())
+}
+
+/// File names commonly used for transparent spacer and tracking GIFs.
+const SPACER_IMAGE_NAMES: &[&str] = &[
+    "spacer.gif",
+    "pixel.gif",
+    "blank.gif",
+    "clear.gif",
+    "transparent.gif",
+    "1x1.gif",
+    "trans.gif",
+];
+
+/// An image is considered a tracking pixel when it's hidden, at most `max_dimension`
+/// pixels wide or high, or a known transparent spacer GIF.
+fn is_tracking_pixel(el: &scraper::ElementRef, max_dimension: u32) -> bool {
+    let tiny = |dimension: Option<u32>| dimension.is_some_and(|d| d <= max_dimension);
+    let is_spacer = el.attr("src").is_some_and(|src| {
+        let src = src.trim().to_lowercase();
+        // The smallest transparent GIF, as a data URL
+        let is_transparent_gif = src.starts_with("data:image/gif;base64,r0lgodlhaqabai");
+        let file_name = src
+            .split(['?', '#'])
+            .next()
+            .and_then(|path| path.rsplit('/').next())
+            .unwrap_or_default();
+        is_transparent_gif || SPACER_IMAGE_NAMES.contains(&file_name)
+    });
+
+    is_hidden(el)
+        || is_spacer
+        || tiny(image_dimension(el, "width"))
+        || tiny(image_dimension(el, "height"))
+}
+
+/// Returns the `src` of every tracking pixel in an HTML body, using the default
+/// size threshold of `detect_tracking_pixels_with_threshold`.
+#[uniffi::export]
+fn detect_tracking_pixels(html: &str) -> Vec<String> {
+    detect_tracking_pixels_with_threshold(html, TRACKING_PIXEL_MAX_DIMENSION)
+}
+
+/// Returns the `src` of every tracking pixel in an HTML body: images that are hidden,
+/// at most `max_dimension` pixels wide or high (2 by default), or transparent spacer GIFs.
+#[uniffi::export]
+fn detect_tracking_pixels_with_threshold(html: &str, max_dimension: u32) -> Vec<String> {
+    static SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("img[src]").unwrap());
+
+    let document = Html::parse_document(html);
+
+    document
+        .select(&SELECTOR)
+        .filter(|el| is_tracking_pixel(el, max_dimension))
+        .filter_map(|el| el.attr("src"))
+        .map(|src| src.trim().to_owned())
+        .collect()
+}
+
+#[derive(uniffi::Record, Debug, PartialEq)]
+struct DataUri {
+    /// Media type without parameters, `text/plain` when the URI doesn't give one
+    mime_type: String,
+    bytes: Vec<u8>,
+}
+
+/// Decodes the `data:` URIs of an HTML body's `src` attributes, like images inlined as
+/// `data:image/png;base64,...`, in document order. Both base64 and percent-encoded
+/// URIs are decoded, and malformed ones are skipped.
+#[uniffi::export]
+fn extract_data_uris(html: &str) -> Vec<DataUri> {
+    static SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("[src]").unwrap());
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let document = Html::parse_document(html);
+
+    document
+        .select(&SELECTOR)
+        .filter_map(|el| {
+            let src = el.attr("src")?.trim();
+            let rest = src
+                .get(..5)
+                .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+                .map(|_| &src[5..])?;
+            let (metadata, data) = rest.split_once(',')?;
+
+            let mut params = metadata.split(';').map(str::trim);
+            let mime_type = params
+                .next()
+                .filter(|typ| typ.contains('/'))
+                .unwrap_or("text/plain")
+                .to_lowercase();
+            let is_base64 = params.any(|param| param.eq_ignore_ascii_case("base64"));
+
+            let bytes = if is_base64 {
+                // Attribute values are often wrapped, and some senders percent-encode `+` and `/`
+                let data: String = String::from_utf8(percent_decode_bytes(data))
+                    .ok()?
+                    .chars()
+                    .filter(|c| !c.is_ascii_whitespace())
+                    .collect();
+                STANDARD.decode(data).ok()?
+            } else {
+                percent_decode_bytes(data)
+            };
+
+            Some(DataUri { mime_type, bytes })
+        })
+        .collect()
+}
+
+/// Returns the meaningful images of an HTML body, skipping tracking pixels.
+#[uniffi::export]
+fn extract_images(html: &str) -> Vec<EmailImage> {
+    static SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("img[src]").unwrap());
+
+    let document = Html::parse_document(html);
+
+    document
+        .select(&SELECTOR)
+        .filter(|el| !is_tracking_pixel(el, TRACKING_PIXEL_MAX_DIMENSION))
+        .filter_map(|el| {
+            let src = el.attr("src")?.trim();
+            if src.is_empty() {
+                return None;
+            }
+
+            let content_id = src
+                .get(..4)
+                .filter(|scheme| scheme.eq_ignore_ascii_case("cid:"))
+                .map(|_| src[4..].to_owned());
+
+            Some(EmailImage {
+                src: src.to_owned(),
+                content_id,
+                alt: el
+                    .attr("alt")
+                    .map(str::trim)
+                    .filter(|alt| !alt.is_empty())
+                    .map(ToOwned::to_owned),
+                width: image_dimension(&el, "width"),
+                height: image_dimension(&el, "height"),
+            })
+        })
+        .collect()
+}
+
+/// Converts an HTML body to plain text, keeping block elements on their own lines
+/// and skipping hidden, script and style content.
+fn html_to_text(html: &str) -> String {
+    fn push_text(element: scraper::ElementRef, out: &mut String) {
+        for child in element.children() {
+            if let Some(text) = child.value().as_text() {
+                out.push_str(text);
+            } else if let Some(child) = scraper::ElementRef::wrap(child) {
+                let name = child.value().name();
+                if matches!(name, "head" | "script" | "style" | "title") || is_hidden(&child) {
+                    continue;
+                }
+
+                let is_block = matches!(
+                    name,
+                    "p" | "div"
+                        | "br"
+                        | "tr"
+                        | "li"
+                        | "table"
+                        | "blockquote"
+                        | "h1"
+                        | "h2"
+                        | "h3"
+                        | "h4"
+                        | "h5"
+                        | "h6"
+                );
+                if is_block {
+                    out.push('\n');
+                }
+                push_text(child, out);
+                if is_block {
+                    out.push('\n');
+                }
+            }
+        }
+    }
+
+    let document = Html::parse_document(html);
+    let mut text = String::new();
+    push_text(document.root_element(), &mut text);
+
+    normalize_whitespace(&text)
+}
+
+/// The readable text of an email: its first non-empty plain text body, or else the
+/// text of its first HTML body, preferring their visible text and with whitespace
+/// normalized. Empty when the email has neither.
+#[uniffi::export]
+fn best_text(email: &Email) -> String {
+    bodies_best_text(&email.text_bodies, &email.html_bodies)
+}
+
+/// `best_text` of an email's bodies, for when the `Email` isn't built yet
+fn bodies_best_text(text_bodies: &[EmailText], html_bodies: &[EmailText]) -> String {
+    let readable = |text: &str| Some(normalize_whitespace(text)).filter(|text| !text.is_empty());
+
+    // HTML-only emails list their HTML part as a text body too
+    text_bodies
+        .iter()
+        .filter(|body| body.content_type != "text/html")
+        .find_map(|body| readable(body.visible.as_deref().unwrap_or(&body.text)))
+        .or_else(|| {
+            html_bodies.iter().find_map(|body| {
+                readable(&html_to_text(body.visible.as_deref().unwrap_or(&body.text)))
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// A one-line preview of at most `max_chars` characters for list rows, from the
+/// email's `best_text` or else its subject. Longer text is cut at the last word
+/// boundary that fits and ends with an ellipsis.
+#[uniffi::export]
+fn snippet(email: &Email, max_chars: u32) -> String {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let one_line = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut text = one_line(&best_text(email));
+    if text.is_empty() {
+        text = one_line(email.subject.as_deref().unwrap_or_default());
+    }
+
+    let max_chars = max_chars as usize;
+    if text.graphemes(true).count() <= max_chars {
+        return text;
+    }
+    if max_chars == 0 {
+        return String::new();
+    }
+
+    // Leaves room for the ellipsis
+    truncate_graphemes(&mut text, max_chars - 1);
+    if let Some(end) = text.rfind(' ').filter(|&end| end > 0) {
+        text.truncate(end);
+    }
+    text.truncate(text.trim_end().len());
+    text.push('…');
+    text
+}
+
+/// Collapses runs of whitespace within each line to a single space, normalizes
+/// newlines to `\n` and keeps at most one blank line in a row, dropping leading
+/// and trailing ones.
+#[uniffi::export]
+fn normalize_whitespace(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !line.is_empty() || lines.last().is_some_and(|l| !l.is_empty()) {
+            lines.push(line);
+        }
+    }
+
+    lines.join("\n").trim().to_owned()
+}
+
+/// Whether an element is styled to take no room, the way preheaders are usually hidden
+/// from clients that ignore `display:none`.
+fn has_zero_size(el: &scraper::ElementRef) -> bool {
+    el.attr("style").is_some_and(|style| {
+        style.split(';').any(|declaration| {
+            let Some((property, value)) = declaration.split_once(':') else {
+                return false;
+            };
+            let property = property.trim().to_lowercase();
+            let value = value.trim().trim_end_matches("!important").trim();
+            let value = value.strip_suffix("px").unwrap_or(value);
+
+            matches!(
+                property.as_str(),
+                "font-size" | "max-height" | "height" | "max-width" | "width" | "opacity"
+            ) && value.parse::<f32>().is_ok_and(|v| v == 0.0)
+        })
+    })
+}
+
+/// Returns the hidden preheader text that newsletters put before their visible
+/// content to be shown in inbox previews, or `None` when the body starts with
+/// visible text.
+#[uniffi::export]
+fn extract_preheader(html: &str) -> Option<String> {
+    /// Zero-width and invisible characters used to pad preheaders
+    fn clean(text: &str) -> String {
+        text.chars()
+            .filter(|c| {
+                !matches!(
+                    c,
+                    '\u{200B}'..='\u{200D}' | '\u{034F}' | '\u{00AD}' | '\u{FEFF}'
+                )
+            })
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// `Some` once the first text is found, holding it when it's hidden
+    fn find(element: scraper::ElementRef) -> Option<Option<String>> {
+        for child in element.children() {
+            if let Some(text) = child.value().as_text() {
+                if !clean(text).is_empty() {
+                    return Some(None);
+                }
+            } else if let Some(child) = scraper::ElementRef::wrap(child) {
+                let name = child.value().name();
+                if matches!(name, "head" | "script" | "style" | "title") {
+                    continue;
+                }
+
+                if is_hidden(&child) || has_zero_size(&child) {
+                    let text = clean(&child.text().collect::<String>());
+                    if !text.is_empty() {
+                        return Some(Some(text));
+                    }
+                } else if let Some(found) = find(child) {
+                    return Some(found);
+                }
+            }
+        }
+        None
+    }
+
+    let document = Html::parse_document(html);
+    find(document.root_element()).flatten()
+}
+
+/// Parses a schema.org `Date` or `DateTime` into a Unix epoch in milliseconds.
+/// Date-times without an offset and plain dates are read as UTC.
+pub(crate) fn parse_schema_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.timestamp_millis())
+        .or_else(|_| {
+            chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+                .or_else(|_| chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M"))
+                .map(|dt| dt.and_utc().timestamp_millis())
+        })
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|d| {
+                d.and_time(chrono::NaiveTime::MIN)
+                    .and_utc()
+                    .timestamp_millis()
+            })
+        })
+        .ok()
+}
+
+#[derive(uniffi::Record, Debug, PartialEq)]
+struct PromoCode {
+    code: String,
+    discount: Option<String>,
+    /// Unix epoch in milliseconds
+    expires: Option<i64>,
+}
+
+/// Finds promo codes in an email.
+///
+/// Codes from schema.org `Offer`/`DiscountOffer` markups and microdata are preferred.
+/// Only when there are none is the visible text scanned, and then only for codes
+/// following a trigger such as "use code" or "promo:".
+#[uniffi::export]
+fn extract_promo_codes(email: &Email) -> Vec<PromoCode> {
+    let mut codes = structured_promo_codes(email);
+
+    if codes.is_empty() {
+        let texts = plain_text_bodies(email)
+            .map(|b| b.visible.clone().unwrap_or_else(|| b.text.clone()))
+            .chain(
+                email
+                    .html_bodies
+                    .iter()
+                    .map(|b| html_to_text(b.visible.as_deref().unwrap_or(&b.text))),
+            );
+
+        for text in texts {
+            codes.extend(promo_codes_in_text(&text));
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    codes.retain(|c| seen.insert(c.code.clone()));
+    codes
+}
+
+/// Products from schema.org `Product` markups, e.g. in shopping and price-drop emails.
+#[uniffi::export]
+fn extract_products(email: &Email) -> Vec<Product> {
+    email
+        .markups
+        .iter()
+        .filter_map(|markup| serde_json::from_str::<serde_json::Value>(markup).ok())
+        .filter_map(|value| parse_product(&value))
+        .collect()
+}
+
+/// Restaurant bookings from schema.org `FoodEstablishmentReservation` markups.
+#[uniffi::export]
+fn extract_dining_reservations(email: &Email) -> Vec<FoodEstablishmentReservation> {
+    email
+        .markups
+        .iter()
+        .filter_map(|markup| serde_json::from_str::<serde_json::Value>(markup).ok())
+        .filter_map(|value| parse_dining_reservation(&value))
+        .collect()
+}
+
+/// Flight bookings and boarding passes from schema.org `FlightReservation` markups.
+#[uniffi::export]
+fn extract_flight_reservations(email: &Email) -> Vec<FlightReservation> {
+    email
+        .markups
+        .iter()
+        .filter_map(|markup| serde_json::from_str::<serde_json::Value>(markup).ok())
+        .filter_map(|value| parse_flight_reservation(&value))
+        .collect()
+}
+
+/// Tickets from schema.org `Ticket` markups and the reservations holding them, e.g.
+/// for event or boarding pass emails.
+#[uniffi::export]
+fn extract_tickets(email: &Email) -> Vec<Ticket> {
+    email
+        .markups
+        .iter()
+        .filter_map(|markup| serde_json::from_str::<serde_json::Value>(markup).ok())
+        .flat_map(|value| parse_tickets(&value))
+        .collect()
+}
+
+/// The invoice of a receipt or bill email, from its schema.org `Invoice` or `Order`
+/// markup.
+#[uniffi::export]
+fn extract_invoice(email: &Email) -> Option<Invoice> {
+    email
+        .markups
+        .iter()
+        .filter_map(|markup| serde_json::from_str::<serde_json::Value>(markup).ok())
+        .find_map(|value| parse_invoice(&value))
+}
+
+/// The sender's identity for a profile card, from the schema.org `Organization`
+/// markup they describe themselves with, or else their `Person` markup.
+#[uniffi::export]
+fn extract_sender_identity(email: &Email) -> Option<Organization> {
+    let mut identities = email
+        .markups
+        .iter()
+        .filter_map(|markup| serde_json::from_str::<serde_json::Value>(markup).ok())
+        .filter_map(|value| parse_sender_identity(&value))
+        .collect::<Vec<_>>();
+
+    let index = identities.iter().position(is_organization).unwrap_or(0);
+    (index < identities.len()).then(|| identities.swap_remove(index))
+}
+
+fn structured_promo_codes(email: &Email) -> Vec<PromoCode> {
+    const OFFER_TYPES: [&str; 2] = ["Offer", "DiscountOffer"];
+
+    let from_markups = email
+        .markups
+        .iter()
+        .filter_map(|markup| serde_json::from_str::<serde_json::Value>(markup).ok())
+        .filter(|value| {
+            let typ = value.get("@type").and_then(serde_json::Value::as_str);
+            typ.is_some_and(|typ| OFFER_TYPES.contains(&typ))
+        })
+        .filter_map(|value| {
+            let field = |names: &[&str]| {
+                names.iter().find_map(|name| match value.get(*name)? {
+                    serde_json::Value::String(s) => Some(s.trim().to_owned()),
+                    serde_json::Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                })
+            };
+
+            Some(PromoCode {
+                code: field(&["discountCode"])?,
+                discount: field(&["discountValue", "discount", "description", "name"]),
+                expires: field(&["availabilityEnds", "validThrough", "priceValidUntil"])
+                    .and_then(|date| parse_schema_date(&date)),
+            })
+        });
+
+    let from_microdata = email
+        .microdata_items
+        .iter()
+        .filter(|item| {
+            item.itemtype.as_deref().is_some_and(|typ| {
+                OFFER_TYPES
+                    .iter()
+                    .any(|offer| typ.trim_end_matches('/').ends_with(&format!("/{offer}")))
+            })
+        })
+        .filter_map(|item| {
+            let field = |names: &[&str]| {
+                names
+                    .iter()
+                    .find_map(|name| item.properties.get(*name))
+                    .map(|s| s.trim().to_owned())
+            };
+
+            Some(PromoCode {
+                code: field(&["discountCode"])?,
+                discount: field(&["discountValue", "discount", "description", "name"]),
+                expires: field(&["availabilityEnds", "validThrough", "priceValidUntil"])
+                    .and_then(|date| parse_schema_date(&date)),
+            })
+        });
+
+    from_markups
+        .chain(from_microdata)
+        .filter(|code| !code.code.is_empty())
+        .collect()
+}
+
+fn promo_codes_in_text(text: &str) -> Vec<PromoCode> {
+    // Only triggers naming a promotion stand on their own. "use code" and "enter
+    // code" also precede verification codes, so they need promo context on the line.
+    static CODE_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r#"(?i:\b(?:(?P<promo>(?:promo(?:tion(?:al)?)?|coupon|discount|voucher|offer)\s+code|promo|coupon)|(?:use|enter)\s+code))\s*[:\-]?\s*["'“‘]?(?P<code>[A-Z0-9][A-Z0-9\-]{3,19})\b"#,
+        )
+        .expect("expression is valid")
+    });
+    static DISCOUNT_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)\d{1,3}\s?%\s?off|[$€£]\s?\d+(?:[.,]\d{2})?\s?off|free shipping")
+            .expect("expression is valid")
+    });
+    static PROMO_CONTEXT_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)\b(?:off|save|savings|discount|sale|offer|deal|promo|coupon)\b")
+            .expect("expression is valid")
+    });
+
+    text.lines()
+        .flat_map(|line| {
+            let discount = DISCOUNT_RE.find(line).map(|m| m.as_str().to_owned());
+            let has_promo_context = discount.is_some() || PROMO_CONTEXT_RE.is_match(line);
+
+            CODE_RE
+                .captures_iter(line)
+                .filter_map(|captures| {
+                    if captures.name("promo").is_none() && !has_promo_context {
+                        return None;
+                    }
+
+                    let code = captures.name("code")?.as_str();
+                    // Codes of only letters need at least 5 of them, so that short words
+                    // in capitals after a trigger ("use code HERE") aren't picked up
+                    let has_digit = code.chars().any(|c| c.is_ascii_digit());
+                    if !has_digit && code.len() < 5 {
+                        return None;
+                    }
+
+                    Some(PromoCode {
+                        code: code.to_owned(),
+                        discount: discount.clone(),
+                        expires: None,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The `List-*` headers of a mailing list email (RFC 2369 and RFC 2919).
+#[derive(uniffi::Record)]
+struct MailingList {
+    /// Identifier from List-Id, e.g. `announce.example.com`
+    id: Option<String>,
+    /// Description in front of the List-Id identifier
+    name: Option<String>,
+    /// URLs to post to the list. Empty when List-Post is `NO`.
+    post: Vec<String>,
+    help: Vec<String>,
+    archive: Vec<String>,
+    subscribe: Vec<String>,
+    /// URLs of List-Unsubscribe. See `Email::unsubscribe` for how to use them.
+    unsubscribe: Vec<String>,
+}
+
+/// Reads the angle-bracketed URLs of a `List-*` header, skipping the ones that
+/// aren't valid. Headers without brackets are read as comma-separated URLs.
+fn list_header_urls(message: &mail_parser::Message<'_>, name: &str) -> Vec<Url> {
+    let value = message
+        .header_raw(name)
+        .unwrap_or_default()
+        .trim()
+        .to_owned()
+        .decode_header();
+
+    // A URL can hold commas itself, like a mailto with several recipients
+    let urls: Vec<&str> = if value.contains('<') {
+        value
+            .split('<')
+            .skip(1)
+            .filter_map(|x| x.split_once('>').map(|(url, _)| url))
+            .collect()
+    } else {
+        value.split(',').collect()
+    };
+
+    urls.into_iter()
+        .filter_map(|x| Url::parse(x.trim()).ok())
+        .collect()
+}
+
+/// Returns `None` for emails without any `List-*` header.
+fn extract_mailing_list(message: &mail_parser::Message<'_>) -> Option<MailingList> {
+    let urls = |name: &str| -> Vec<String> {
+        list_header_urls(message, name)
+            .into_iter()
+            .map(String::from)
+            .collect()
+    };
+
+    let list_id = message
+        .header_raw("list-id")
+        .map(|x| x.trim().to_owned().decode_header());
+    let (id, name) = match list_id.as_deref().and_then(|x| x.rsplit_once('<')) {
+        Some((name, id)) => (
+            Some(id.trim_end_matches('>').trim().to_owned()),
+            Some(name.trim().trim_matches('"').trim().to_owned()).filter(|n| !n.is_empty()),
+        ),
+        None => (list_id.filter(|x| !x.is_empty()), None),
+    };
+
+    let list = MailingList {
+        id,
+        name,
+        post: urls("list-post"),
+        help: urls("list-help"),
+        archive: urls("list-archive"),
+        subscribe: urls("list-subscribe"),
+        unsubscribe: urls("list-unsubscribe"),
+    };
+
+    let is_empty = list.id.is_none()
+        && list.post.is_empty()
+        && list.help.is_empty()
+        && list.archive.is_empty()
+        && list.subscribe.is_empty()
+        && list.unsubscribe.is_empty();
+    (!is_empty).then_some(list)
+}
+
+#[derive(uniffi::Record)]
+struct Unsubscribe {
+    get: Option<String>,
+    website: Option<String>,
+    /// Body link whose text asks to unsubscribe, as opposed to `manage_preferences_url`
+    #[uniffi(default = None)]
+    unsubscribe_url: Option<String>,
+    /// Body link to the sender's email preferences or subscription settings, which
+    /// usually lets users pick what they receive instead of opting out entirely
+    #[uniffi(default = None)]
+    manage_preferences_url: Option<String>,
+    post: Option<UnsubscribePost>,
+    email: Option<UnsubscribeEmail>,
+    /// Where the unsubscribe data came from, `None` when there's none
+    #[uniffi(default = None)]
+    source: Option<UnsubscribeSource>,
+}
+
+#[derive(uniffi::Enum, Debug, PartialEq)]
+enum UnsubscribeSource {
+    /// The List-Unsubscribe header, which senders have to honor (RFC 2369, RFC 8058)
+    Header,
+    /// Unsubscribe links in the HTML body, found when the email has no List-Unsubscribe.
+    /// `get` is only set from an anchor reading like an unsubscribe link, which may
+    /// lead to a page asking for confirmation.
+    Body,
+}
+
+#[derive(uniffi::Record)]
+struct UnsubscribePost {
+    url: String,
+    body: String,
+}
+
+#[derive(uniffi::Record)]
+struct UnsubscribeEmail {
+    /// The first recipient
+    email: String,
+    /// Every address of the mailto URL, from its comma-separated path and any `to`
+    /// header, all of which should receive the unsubscribe email
+    #[uniffi(default = [])]
+    recipients: Vec<String>,
+    /// Every `hfname=hfvalue` pair of the mailto URL, including `subject` and `body`
+    headers: Vec<Header>,
+    subject: Option<String>,
+    body: Option<String>,
+}
+
+/// Decodes the percent-encoded parts of a mailto URL. Unlike form encoding,
+/// a `+` is kept as is (RFC 6068).
+fn percent_decode(value: &str) -> String {
+    String::from_utf8_lossy(&percent_decode_bytes(value)).into_owned()
+}
+
+/// Decodes `%XX` escapes, keeping `%` as is when it isn't followed by two hex digits
+fn percent_decode_bytes(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn extract_unsubscribe(message: &mail_parser::Message<'_>) -> Unsubscribe {
+    let has_list_unsubscribe = message
+        .header_raw("list-unsubscribe")
+        .is_some_and(|x| !x.trim().is_empty());
+    if !has_list_unsubscribe {
+        let links = find_unsubscribe_links(message);
+        return Unsubscribe {
+            get: links.unsubscribe.clone(),
+            post: None,
+            email: None,
+            source: links.website.is_some().then_some(UnsubscribeSource::Body),
+            website: links.website,
+            unsubscribe_url: links.unsubscribe,
+            manage_preferences_url: links.manage_preferences,
+        };
+    }
+
+    let urls = list_header_urls(message, "list-unsubscribe");
+    let url = urls
+        .iter()
+        .find(|u| u.scheme() == "http" || u.scheme() == "https");
+    let list_unsubscribe_post = message
+        .header_raw("list-unsubscribe-post")
+        .map(|x| x.trim().to_owned().decode_header());
+
+    let get = match (&url, &list_unsubscribe_post) {
+        (Some(url), None) => Some(url.to_string()),
+        _ => None,
+    };
+
+    let post = match (&url, &list_unsubscribe_post) {
+        (Some(url), Some(post)) => Some(UnsubscribePost {
+            url: url.to_string(),
+            body: post.to_owned(),
+        }),
+        _ => None,
+    };
+
+    let email = urls.iter().find(|u| u.scheme() == "mailto").map(|url| {
+        let headers: Vec<Header> = url
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                Header {
+                    name: percent_decode(name),
+                    value: percent_decode(value),
+                }
+            })
+            .collect();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|h| h.name.eq_ignore_ascii_case(name))
+                .map(|h| h.value.to_owned())
+        };
+
+        let recipients: Vec<String> = url
+            .path()
+            .split(',')
+            .map(percent_decode)
+            .chain(
+                headers
+                    .iter()
+                    .filter(|h| h.name.eq_ignore_ascii_case("to"))
+                    .flat_map(|h| h.value.split(',').map(ToOwned::to_owned)),
+            )
+            .map(|address| address.trim().to_owned())
+            .filter(|address| !address.is_empty())
+            .collect();
+
+        UnsubscribeEmail {
+            email: recipients.first().cloned().unwrap_or_default(),
+            recipients,
+            subject: header("subject"),
+            body: header("body"),
+            headers,
+        }
+    });
+
+    let links = find_unsubscribe_links(message);
+
+    Unsubscribe {
+        get,
+        post,
+        email,
+        website: links.website,
+        unsubscribe_url: links.unsubscribe,
+        manage_preferences_url: links.manage_preferences,
+        source: Some(UnsubscribeSource::Header),
+    }
+}
+
+/// Unsubscribe links found in an HTML body
+#[derive(Default)]
+struct UnsubscribeLinks {
+    /// The anchor with unsubscribe or preferences text, or else the first whose
+    /// parent has it, like a footer paragraph reading "Don't want these emails?
+    /// Click here"
+    website: Option<String>,
+    /// The anchor whose own text asks to unsubscribe
+    unsubscribe: Option<String>,
+    manage_preferences: Option<String>,
+}
+
+/// The unsubscribe links of the first HTML body that has any. Anchors are told
+/// apart by their own text, or for `website` and `manage_preferences` by their
+/// parent's when it only mentions one kind.
+fn find_unsubscribe_links(message: &mail_parser::Message<'_>) -> UnsubscribeLinks {
+    static ANCHOR_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a").unwrap());
+
+    const UNSUBSCRIBE_KEYWORDS: &[&str] = &[
+        "unsubscribe",
+        "opt out",
+        "opt-out",
+        "stop receiving",
+        "cancel subscription",
+    ];
+    const PREFERENCES_KEYWORDS: &[&str] = &[
+        "email preferences",
+        "email settings",
+        "manage preferences",
+        "manage subscription",
+        "update preferences",
+        "subscription settings",
+        "preference center",
+    ];
+
+    /// Whether an element's text asks to unsubscribe, and whether it mentions preferences
+    #[derive(Clone, Copy)]
+    struct Kinds {
+        unsubscribe: bool,
+        preferences: bool,
+    }
+
+    let kinds = |el: &scraper::ElementRef| {
+        let text = el.text().collect::<String>().to_lowercase();
+        let contains = |keywords: &[&str]| keywords.iter().any(|keyword| text.contains(keyword));
+        Kinds {
+            unsubscribe: contains(UNSUBSCRIBE_KEYWORDS),
+            preferences: contains(PREFERENCES_KEYWORDS),
+        }
+    };
+
+    message
+        .html_bodies()
+        .map(body_text)
+        .find_map(|html| {
+            let document = Html::parse_document(&html);
+            let links: Vec<(Kinds, Kinds, String)> = document
+                .select(&ANCHOR_SELECTOR)
+                .filter_map(|el| {
+                    let href = el.attr("href")?.trim();
+                    if !(href.starts_with("http://") || href.starts_with("https://")) {
+                        return None;
+                    }
+
+                    let own = kinds(&el);
+                    let parent = el.parent().and_then(scraper::ElementRef::wrap).map_or(
+                        Kinds {
+                            unsubscribe: false,
+                            preferences: false,
+                        },
+                        |parent| kinds(&parent),
+                    );
+                    let any = |kinds: Kinds| kinds.unsubscribe || kinds.preferences;
+                    (any(own) || any(parent)).then(|| (own, parent, href.to_owned()))
+                })
+                .collect();
+            if links.is_empty() {
+                return None;
+            }
+
+            let find = |is_kind: &dyn Fn(Kinds) -> bool, parent_fallback: bool| {
+                let own_text = links.iter().find(|(own, _, _)| is_kind(*own));
+                let parent_text = || {
+                    links.iter().find(|(own, parent, _)| {
+                        parent_fallback && !own.unsubscribe && !own.preferences && is_kind(*parent)
+                    })
+                };
+                own_text
+                    .or_else(parent_text)
+                    .map(|(_, _, href)| href.clone())
+            };
+
+            Some(UnsubscribeLinks {
+                website: find(&|kinds| kinds.unsubscribe || kinds.preferences, true),
+                // Anchors like "Unsubscribe or manage preferences" count as unsubscribing
+                unsubscribe: find(&|kinds| kinds.unsubscribe, false),
+                manage_preferences: find(&|kinds| kinds.preferences && !kinds.unsubscribe, true),
+            })
+        })
+        .unwrap_or_default()
+}
+
+#[derive(uniffi::Record)]
+struct ReceivedHop {
+    from: Option<String>,
+    by: Option<String>,
+    /// Unix epoch in seconds
+    timestamp: Option<i64>,
+}
+
+fn extract_received_chain(message: &mail_parser::Message<'_>) -> Vec<ReceivedHop> {
+    message
+        .headers()
+        .iter()
+        .filter(|h| h.name == HeaderName::Received)
+        .map(|h| match h.value.as_received() {
+            Some(received) => ReceivedHop {
+                from: received.from().map(ToString::to_string),
+                by: received.by().map(ToString::to_string),
+                timestamp: received
+                    .date()
+                    .filter(|d| d.is_valid())
+                    .map(|d| d.to_timestamp()),
+            },
+            None => {
+                let raw = message
+                    .raw_message()
+                    .get(h.offset_start..h.offset_end)
+                    .map(String::from_utf8_lossy)
+                    .unwrap_or_default();
+                parse_received_fallback(&raw)
+            }
+        })
+        .collect()
+}
+
+/// Extracts only the `from`, `by` and `; date` parts of a Received header that
+/// `mail_parser` couldn't make sense of.
+fn parse_received_fallback(raw: &str) -> ReceivedHop {
+    static COMMENT_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\([^)]*\)").expect("expression is valid"));
+    static HOST_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?i)\b(from|by)\s+([^\s;]+)").expect("expression is valid"));
+
+    let (clauses, date) = match raw.rsplit_once(';') {
+        Some((clauses, date)) => (clauses, Some(date)),
+        None => (raw, None),
+    };
+    let clauses = COMMENT_RE.replace_all(clauses, " ");
+
+    let mut from = None;
+    let mut by = None;
+    for captures in HOST_RE.captures_iter(&clauses) {
+        let host = captures[2].to_owned();
+        if captures[1].eq_ignore_ascii_case("from") {
+            from.get_or_insert(host);
+        } else {
+            by.get_or_insert(host);
+        }
+    }
+
+    let timestamp = date
+        .and_then(|date| mail_parser::DateTime::parse_rfc822(date.trim()))
+        .filter(|d| d.is_valid())
+        .map(|d| d.to_timestamp());
+
+    ReceivedHop {
+        from,
+        by,
+        timestamp,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encode(raw: impl AsRef<[u8]>) -> String {
+        use base64::{engine::general_purpose::URL_SAFE, Engine};
+
+        URL_SAFE.encode(raw)
+    }
+
+    #[test]
+    fn do_test() {
+        let responses = std::fs::read_dir("responses/allspark")
+            .unwrap()
+            .filter_map(|x| x.ok())
+            .map(|x| x.path());
+
+        for path in responses {
+            let file = std::fs::read_to_string(path).unwrap();
+            parse_batch_response(file);
+        }
+    }
+
+    #[test]
+    fn calendar_iso_times() {
+        let ics = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:tz\r
+SEQUENCE:3\r
+RECURRENCE-ID:20250108T033000Z\r
+DTSTART;TZID=Asia/Kolkata:20250101T090000\r
+DTEND:20250101T043000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:floating\r
+DTSTART:20250101T090000\r
+TRANSP:TRANSPARENT\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:allday\r
+DTSTART;VALUE=DATE:20250101\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = parse_events(ics).unwrap();
+        let starts: Vec<_> = events.iter().map(|e| e.start_iso.as_deref()).collect();
+
+        assert_eq!(
+            starts,
+            [
+                Some("2025-01-01T09:00:00+05:30"),
+                Some("2025-01-01T09:00:00"),
+                Some("2025-01-01"),
+            ]
+        );
+        assert_eq!(events[0].end_iso.as_deref(), Some("2025-01-01T04:30:00Z"));
+        assert_eq!(events[0].transparency, None);
+        assert_eq!(events[0].sequence, Some(3));
+        assert_eq!(events[0].recurrence_id, Some(1736307000000));
+        assert_eq!(events[1].sequence, None);
+        assert_eq!(events[1].transparency, Some(Transparency::Transparent));
+    }
+
+    #[test]
+    fn markup_type_filter() {
+        let types = vec!["Event".to_owned(), "Order".to_owned()];
+
+        assert!(markup_has_type(r#"{"@type":"Event"}"#, &types));
+        assert!(markup_has_type(r#"{"@type":["Thing","Order"]}"#, &types));
+        assert!(!markup_has_type(r#"{"@type":"event"}"#, &types));
+        assert!(!markup_has_type(r#"{"name":"Event"}"#, &types));
+    }
+
+    #[test]
+    fn date_falls_back_to_received() {
+        let raw = "Received: from mx.example.com by mx.vella.ai; Tue, 1 Jul 2025 10:00:00 +0000\r
+From: a@example.com\r
+To: b@example.com\r
+Date: sometime last week\r
+\r
+Hello\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+
+        assert_eq!(email.date, Some(1751364000));
+        assert_eq!(email.date_raw.as_deref(), Some("sometime last week"));
+    }
+
+    #[test]
+    fn received_chain() {
+        let raw = "Received: from relay.example.com (relay.example.com [10.0.0.1])\r
+\tby mx.vella.ai with ESMTPS id abc; Tue, 1 Jul 2025 10:00:05 +0000\r
+Received: from origin.example.com by relay.example.com; Tue, 1 Jul 2025 10:00:00 +0000\r
+From: a@example.com\r
+To: b@example.com\r
+\r
+Hello\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        let hops: Vec<_> = email
+            .received_chain
+            .iter()
+            .map(|h| (h.from.as_deref(), h.by.as_deref(), h.timestamp))
+            .collect();
+
+        assert_eq!(
+            hops,
+            [
+                (
+                    Some("relay.example.com"),
+                    Some("mx.vella.ai"),
+                    Some(1751364005)
+                ),
+                (
+                    Some("origin.example.com"),
+                    Some("relay.example.com"),
+                    Some(1751364000)
+                ),
+            ]
+        );
+
+        let hop = parse_received_fallback(" by localhost (Postfix, from userid 0); garbage");
+        assert_eq!(hop.from, None);
+        assert_eq!(hop.by.as_deref(), Some("localhost"));
+        assert_eq!(hop.timestamp, None);
+    }
+
+    #[test]
+    fn preview_chars_keeps_graphemes_whole() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/plain; charset=utf-8\r
+\r
+Cafe\u{301} 👩‍👩‍👧 time\r
+";
+        let options = ParseOptions {
+            preview_chars: Some(6),
+            ..Default::default()
+        };
+        let email = parse_email_with_options(encode(raw), options).unwrap();
+
+        assert_eq!(email.text_bodies[0].text, "Cafe\u{301} 👩‍👩‍👧");
+        assert!(email.text_bodies[0].truncated);
+    }
+
+    #[test]
+    fn detects_text_direction() {
+        assert_eq!(text_direction("Hello"), TextDirection::Ltr);
+        assert_eq!(text_direction("123 مرحبا hello"), TextDirection::Rtl);
+        assert_eq!(text_direction("\n\nשלום"), TextDirection::Rtl);
+        assert_eq!(text_direction("12:30 - !"), TextDirection::Neutral);
+    }
+
+    #[test]
+    fn normalizes_subject() {
+        assert_eq!(
+            normalize_subject("Re: RE: Fwd:  Lunch   plans"),
+            "Lunch plans"
+        );
+        assert_eq!(normalize_subject("Re[2]: AW: WG: Angebot"), "Angebot");
+        assert_eq!(normalize_subject("FW : Report"), "Report");
+        assert_eq!(normalize_subject("回复：会议"), "会议");
+        assert_eq!(
+            normalize_subject("Review: Q3 numbers"),
+            "Review: Q3 numbers"
+        );
+        assert_eq!(
+            normalize_subject("Meeting re: budget"),
+            "Meeting re: budget"
+        );
+        assert_eq!(normalize_subject("R: the language"), "R: the language");
+        assert_eq!(normalize_subject("I: Overview"), "I: Overview");
+        assert_eq!(normalize_subject("Rif: I: Ordine"), "I: Ordine");
+    }
+
+    #[test]
+    fn counts_only_real_attachments() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/mixed; boundary=outer\r
+\r
+--outer\r
+Content-Type: multipart/related; boundary=inner\r
+\r
+--inner\r
+Content-Type: text/html\r
+\r
+<img src=\"cid:logo@x\">\r
+--inner\r
+Content-Type: image/png\r
+Content-ID: <logo@x>\r
+Content-Disposition: inline\r
+Content-Transfer-Encoding: base64\r
+\r
+iVBORw0KGgo=\r
+--inner--\r
+--outer\r
+Content-Type: text/calendar; method=REQUEST\r
+\r
+BEGIN:VCALENDAR\r
+END:VCALENDAR\r
+--outer\r
+Content-Type: application/pdf; name=invoice.pdf\r
+Content-Disposition: attachment; filename=invoice.pdf\r
+Content-Transfer-Encoding: base64\r
+\r
+JVBERi0=\r
+--outer--\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+
+        assert!(email.has_attachments);
+        assert_eq!(email.attachment_count, 1);
+    }
+
+    #[test]
+    fn extracts_images_without_pixels() {
+        let html = r#"
+            <img src="https://example.com/hero.png" alt="Summer sale" width="600" style="height: 300px">
+            <img src="cid:logo@example.com" width="120px" height="40">
+            <img src="https://track.example.com/open.gif" width="1" height="1">
+            <img src="https://track.example.com/hidden.gif" style="display: none">
+        "#;
+
+        let images = extract_images(html);
+        let images: Vec<_> = images
+            .iter()
+            .map(|i| {
+                (
+                    i.src.as_str(),
+                    i.content_id.as_deref(),
+                    i.alt.as_deref(),
+                    i.width,
+                    i.height,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            images,
+            [
+                (
+                    "https://example.com/hero.png",
+                    None,
+                    Some("Summer sale"),
+                    Some(600),
+                    Some(300)
+                ),
+                (
+                    "cid:logo@example.com",
+                    Some("logo@example.com"),
+                    None,
+                    Some(120),
+                    Some(40)
+                ),
+            ]
+        );
+        assert_eq!(detect_tracking_pixels(html).len(), 2);
+    }
+
+    #[test]
+    fn content_hash_ignores_volatile_headers() {
+        let first = "From: a@example.com\r
+To: b@example.com, c@example.com\r
+Subject: Re: Hello\r
+Date: Tue, 1 Jul 2025 10:00:00 +0000\r
+X-Trace: 1\r
+Content-Type: text/html\r
+\r
+<p>Hi</p><img src=\"https://t.example.com/1\" width=\"1\" height=\"1\">\r
+";
+        let second = "X-Trace: 2\r
+Date: Tue, 1 Jul 2025 10:00:00 +0000\r
+Subject: Hello\r
+To: C@example.com, b@example.com\r
+From: a@example.com\r
+Content-Type: text/html\r
+\r
+<p>Hi</p><img src=\"https://t.example.com/2\" width=\"1\" height=\"1\">\r
+";
+        let first = parse_email(encode(first)).unwrap();
+        let second = parse_email(encode(second)).unwrap();
+
+        assert_eq!(content_hash(&first), content_hash(&second));
+        assert_eq!(content_hash(&first).len(), 64);
+    }
+
+    #[test]
+    fn decodes_base64_variants() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        // Encodes to a string containing both `+` and `/`
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\nSubject: ~~~???\r\n\r\n>>>\r\n";
+        let standard = STANDARD.encode(raw);
+        let wrapped = format!("{}\r\n{}", &standard[..40], &standard[40..]);
+        assert!(standard.contains('+') && standard.contains('/'));
+
+        let decode = |input: &str, variant| base64_decode(input, variant).ok();
+        assert_eq!(
+            decode(&standard, Base64Variant::Standard).as_deref(),
+            Some(raw)
+        );
+        assert_eq!(decode(&wrapped, Base64Variant::Auto).as_deref(), Some(raw));
+        assert_eq!(
+            decode(&encode(raw), Base64Variant::Auto).as_deref(),
+            Some(raw)
+        );
+    }
+
+    #[test]
+    fn finds_promo_codes_in_text() {
+        let codes = promo_codes_in_text("Get 20% off today! Use code SAVE20 at checkout.\nPromo: SUMMERFUN\nOur CODE OF CONDUCT has changed.");
+        let codes: Vec<_> = codes
+            .iter()
+            .map(|c| (c.code.as_str(), c.discount.as_deref()))
+            .collect();
+
+        assert_eq!(codes, [("SAVE20", Some("20% off")), ("SUMMERFUN", None)]);
+
+        for text in [
+            "Your verification code 482913",
+            "Your zip code 94107",
+            "Use the code BELOW",
+            "Enter code 482913 to sign in",
+        ] {
+            assert!(promo_codes_in_text(text).is_empty(), "{text}");
+        }
+        assert_eq!(
+            promo_codes_in_text("Enter code FALL2025 to save")[0].code,
+            "FALL2025"
+        );
+
+        // HTML-only emails are scanned as visible text, not as markup
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+\r
+<!-- promo: INTERNAL42 -->\r
+<p>Tom &amp; Jerry&nbsp;say: use code <b>SAVE20</b> for 20% off</p>\r
+";
+        let codes = extract_promo_codes(&parse_email(encode(raw)).unwrap());
+        let codes: Vec<_> = codes.iter().map(|c| c.code.as_str()).collect();
+        assert_eq!(codes, ["SAVE20"]);
+    }
+
+    #[test]
+    fn converts_html_to_text() {
+        let html = "<html><head><style>p {}</style></head><body><div style=\"display:none\">preheader</div><p>Hello   <b>there</b></p><p>Second</p></body></html>";
+
+        assert_eq!(html_to_text(html), "Hello there\n\nSecond");
+    }
+
+    #[test]
+    fn registered_reply_separators() {
+        // Registered patterns apply to every test in the binary, so this one is unique
+        let body = "Sounds good!\n\n-----Vella test separator-----\nFrom: someone";
+        assert_eq!(parse_visible_text(body), None);
+
+        assert!(register_reply_separator("(".to_owned()).is_err());
+        register_reply_separator(r"-{5}Vella test separator-{5}".to_owned()).unwrap();
+
+        assert_eq!(parse_visible_text(body).as_deref(), Some("Sounds good!"));
+        assert_eq!(
+            parse_visible_text("Yes\nOn Tue, 1 Jul 2025 at 10:00 someone wrote:").as_deref(),
+            Some("Yes")
+        );
+    }
+
+    #[test]
+    fn converts_event_markups() {
+        let markup = serde_json::json!({
+            "@context": "https://schema.org",
+            "@type": "EventReservation",
+            "reservationFor": {
+                "@type": "MusicEvent",
+                "name": "Concert",
+                "startDate": "2025-07-01T20:00:00+02:00",
+                "url": "https://tickets.example.com/1",
+                "location": {
+                    "@type": "Place",
+                    "name": "Arena",
+                    "address": {"@type": "PostalAddress", "streetAddress": "1 Main St", "addressLocality": "Berlin"}
+                }
+            }
+        });
+        let event = markup_calendar_event(&markup).unwrap();
+
+        assert_eq!(event.summary.as_deref(), Some("Concert"));
+        assert_eq!(event.start, Some(1751392800000));
+        assert_eq!(
+            event.start_iso.as_deref(),
+            Some("2025-07-01T20:00:00+02:00")
+        );
+        assert_eq!(event.location.as_deref(), Some("Arena, 1 Main St, Berlin"));
+        assert_eq!(event.url.as_deref(), Some("https://tickets.example.com/1"));
+
+        let order = serde_json::json!({"@type": "Order"});
+        assert!(markup_calendar_event(&order).is_none());
+    }
+
+    #[test]
+    fn strips_trailing_footers() {
+        assert_eq!(
+            strip_footers("See you then!\n\nGet Outlook for iOS<https://aka.ms/o0ukef>\n"),
+            "See you then!"
+        );
+        assert_eq!(
+            strip_footers(
+                "Thanks\r\n\r\nSent from my iPhone\r\n\r\nSent from Yahoo Mail on Android"
+            ),
+            "Thanks"
+        );
+        assert_eq!(
+            strip_footers("Sent from my iPhone, so excuse typos.\nMore later"),
+            "Sent from my iPhone, so excuse typos.\nMore later"
+        );
+        assert_eq!(strip_footers("Sent from my iPhone"), "Sent from my iPhone");
+    }
+
+    #[test]
+    fn selects_primary_bodies() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/alternative; boundary=alt\r
+\r
+--alt\r
+Content-Type: text/plain\r
+\r
+Hello there\r
+--alt\r
+Content-Type: text/html\r
+\r
+<p>Hi</p>\r
+--alt\r
+Content-Type: text/html\r
+\r
+<p>Hello <b>there</b></p>\r
+--alt--\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+
+        let html = primary_html_body(&email).unwrap();
+        assert!(html.text.contains("<b>there</b>"));
+        assert_eq!(
+            primary_text_body(&email).unwrap().text.trim(),
+            "Hello there"
+        );
+
+        // The mirrored HTML part decodes its entities, so it differs from the HTML body
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+\r
+<p>Tom &amp; Jerry&nbsp;are back</p>\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        assert!(primary_html_body(&email).is_some());
+        assert!(primary_text_body(&email).is_none());
+    }
+
+    #[test]
+    fn parses_priority() {
+        let priority = |headers: &str| {
+            let raw = format!("From: a@example.com\r\nTo: b@example.com\r\n{headers}\r\nHi\r\n");
+            parse_email(encode(&raw)).unwrap().priority
+        };
+
+        assert_eq!(
+            priority("X-Priority: 1 (Highest)\r\n"),
+            Some(MessagePriority::High)
+        );
+        assert_eq!(priority("X-Priority: 5\r\n"), Some(MessagePriority::Low));
+        assert_eq!(
+            priority("Priority: non-urgent\r\n"),
+            Some(MessagePriority::Low)
+        );
+        assert_eq!(
+            priority("X-Priority: 1\r\nImportance: Normal\r\n"),
+            Some(MessagePriority::Normal)
+        );
+        assert_eq!(priority(""), None);
+    }
+
+    #[test]
+    fn derives_display_names() {
+        let name = |name: Option<&str>, address: &str| {
+            display_name(&EmailAddress {
+                name: name.map(ToOwned::to_owned),
+                address: address.to_owned(),
+                is_group: false,
+                group: None,
+            })
+        };
+
+        assert_eq!(name(Some("ACME STORE"), "deals@acme.com"), "ACME STORE");
+        assert_eq!(name(None, "john.doe+news@example.com"), "John Doe");
+        assert_eq!(
+            name(Some("noreply"), "jane_SMITH-2@example.com"),
+            "Jane Smith"
+        );
+        assert_eq!(name(Some("a@b.com"), "no-reply@mail.github.com"), "Github");
+        assert_eq!(name(None, "info@shop.co.uk"), "Shop");
+    }
+
+    #[test]
+    fn unsubscribe_mailto_fields() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+List-Unsubscribe: <mailto:leave@example.com?subject=Unsubscribe%20me&body=id%3D42+x>\r
+\r
+Hi\r
+";
+        let email = parse_email(encode(raw)).unwrap().unsubscribe.email.unwrap();
+
+        assert_eq!(email.email, "leave@example.com");
+        assert_eq!(email.subject.as_deref(), Some("Unsubscribe me"));
+        assert_eq!(email.body.as_deref(), Some("id=42+x"));
+        assert_eq!(email.headers.len(), 2);
+        assert_eq!(email.recipients, ["leave@example.com"]);
+    }
+
+    #[test]
+    fn unsubscribe_mailto_multiple_recipients() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+List-Unsubscribe: <mailto:leave@example.com,list-owner%40example.org?subject=unsubscribe&to=abuse@example.net>\r
+\r
+Hi\r
+";
+        let email = parse_email(encode(raw)).unwrap().unsubscribe.email.unwrap();
+
+        assert_eq!(email.email, "leave@example.com");
+        assert_eq!(
+            email.recipients,
+            [
+                "leave@example.com",
+                "list-owner@example.org",
+                "abuse@example.net"
+            ]
+        );
+        assert_eq!(email.subject.as_deref(), Some("unsubscribe"));
+    }
+
+    #[test]
+    fn groups_threads() {
+        let message = |id: &str, thread_id: &str, headers: &str| {
+            let raw = format!("From: a@example.com\r\nTo: b@example.com\r\n{headers}\r\nHi\r\n");
+            GmailMessage {
+                id: id.to_owned(),
+                threadId: thread_id.to_owned(),
+                labelIds: vec![],
+                snippet: parse_text(String::new()),
+                sizeEstimate: 0,
+                data: parse_email(encode(&raw)).unwrap(),
+                historyId: String::new(),
+                internalDate: String::new(),
+            }
+        };
+
+        let threads = group_threads(vec![
+            message(
+                "3",
+                "",
+                "Message-ID: <c@x>\r\nIn-Reply-To: <a@x>\r\nSubject: Re: Plans\r\nDate: Wed, 3 Jan 2024 00:00:00 +0000\r\n",
+            ),
+            message(
+                "1",
+                "t1",
+                "Message-ID: <a@x>\r\nSubject: Plans\r\nDate: Mon, 1 Jan 2024 00:00:00 +0000\r\n",
+            ),
+            message(
+                "2",
+                "t2",
+                "Message-ID: <b@x>\r\nSubject: Other\r\nDate: Tue, 2 Jan 2024 00:00:00 +0000\r\n",
+            ),
+        ]);
+
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].thread_id, "t1");
+        assert_eq!(threads[0].subject, "Plans");
+        assert_eq!(threads[0].latest_date, Some(1704240000));
+        let ids: Vec<&str> = threads[0].messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["1", "3"]);
+    }
+
+    #[test]
+    fn decodes_undeclared_quoted_printable() {
+        // The part is quoted-printable encoded but has no Content-Transfer-Encoding
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/plain; charset=utf-8\r
+\r
+Your order=20is on its way. Track it at https://example.com/track?id=3D42 or=\r
+ reply to this email.=20Caf=C3=A9\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+
+        assert_eq!(
+            email.text_bodies[0].text.trim(),
+            "Your order is on its way. Track it at https://example.com/track?id=42 or reply to this email. Café"
+        );
+
+        let declared = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/plain; charset=utf-8\r
+Content-Transfer-Encoding: quoted-printable\r
+\r
+a=3D1 and b=3D2=20\r
+";
+        let email = parse_email(encode(declared)).unwrap();
+        assert_eq!(email.text_bodies[0].text.trim(), "a=1 and b=2");
+
+        assert_eq!(decode_quoted_printable("x=ZZ=E2=82"), "x=ZZ\u{FFFD}");
+        assert!(!looks_quoted_printable("https://example.com/?a=20"));
+    }
+
+    #[test]
+    fn keeps_declared_encodings_decoded_once() {
+        let seven_bit = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+Content-Transfer-Encoding: 7bit\r
+\r
+<img src=\"https://example.com/i.png?w=200&h=200\"> from=2024 x=20\r
+";
+        let email = parse_email(encode(seven_bit)).unwrap();
+        assert!(email.html_bodies[0]
+            .text
+            .contains("https://example.com/i.png?w=200&h=200\"> from=2024 x=20"));
+
+        let quoted_printable = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+Content-Transfer-Encoding: quoted-printable\r
+\r
+<img src=3D\"https://example.com/i.png?w=3D200&h=3D200\"> from=3D2024=\r
+ x=3D20\r
+";
+        let email = parse_email(encode(quoted_printable)).unwrap();
+        assert!(email.html_bodies[0]
+            .text
+            .contains("https://example.com/i.png?w=200&h=200\"> from=2024 x=20"));
+
+        // Undeclared, but a single line without soft breaks isn't enough evidence
+        let undeclared = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+\r
+<img src=\"https://example.com/i.png?w=200&h=200\">\r
+";
+        let email = parse_email(encode(undeclared)).unwrap();
+        assert!(email.html_bodies[0].text.contains("?w=200&h=200"));
+    }
+
+    #[test]
+    fn parses_headers_only() {
+        let raw = "From: Jane <jane@example.com>\r
+To: b@example.com\r
+Subject: Hello\r
+Message-ID: <1@example.com>\r
+Date: Mon, 1 Jan 2024 00:00:00 +0000\r
+\r
+Hi\r
+";
+        let summary = parse_email_headers(encode(raw)).unwrap();
+
+        assert_eq!(summary.from.name.as_deref(), Some("Jane"));
+        assert_eq!(summary.to.address, "b@example.com");
+        assert_eq!(summary.subject.as_deref(), Some("Hello"));
+        assert_eq!(summary.date, Some(1704067200));
+        assert_eq!(summary.message_id.as_deref(), Some("1@example.com"));
+        assert!(matches!(
+            parse_email_headers(encode("To: b@example.com\r\n\r\nHi")),
+            Err(ParserError::NoFromHeader)
+        ));
+    }
+
+    /// Compares `parse_email_headers` against `parse_email` on a large email.
+    /// Run with `cargo test --release -- --ignored --nocapture bench_parse_headers`.
+    #[test]
+    #[ignore]
+    fn bench_parse_headers() {
+        let html = "<p>Hello <a href=\"https://example.com\">there</a></p>\r\n".repeat(20_000);
+        let attachment = "QUJDREVGR0hJSktMTU5PUFFSU1RVVldYWVo=\r\n".repeat(20_000);
+        let raw = format!(
+            "From: a@example.com\r
+To: b@example.com\r
+Subject: Large\r
+Content-Type: multipart/mixed; boundary=b\r
+\r
+--b\r
+Content-Type: text/html\r
+\r
+{html}\r
+--b\r
+Content-Type: application/pdf\r
+Content-Disposition: attachment; filename=a.pdf\r
+Content-Transfer-Encoding: base64\r
+\r
+{attachment}\r
+--b--\r
+"
+        );
+        let raw = encode(&raw);
+
+        let time = |parse: &dyn Fn()| {
+            let start = std::time::Instant::now();
+            for _ in 0..10 {
+                parse();
+            }
+            start.elapsed() / 10
+        };
+        let full = time(&|| {
+            parse_email(raw.clone()).unwrap();
+        });
+        let headers = time(&|| {
+            parse_email_headers(raw.clone()).unwrap();
+        });
+
+        println!("parse_email: {full:?}, parse_email_headers: {headers:?}");
+        assert!(headers < full);
+    }
+
+    /// Times `parse_email` over a directory of raw `.eml` emails, `responses/samples`
+    /// unless `VELLA_BENCH_EMAILS` points elsewhere. Run with
+    /// `cargo test --release -- --ignored --nocapture bench_parse_directory`.
+    #[test]
+    #[ignore]
+    fn bench_parse_directory() {
+        let dir =
+            std::env::var("VELLA_BENCH_EMAILS").unwrap_or_else(|_| "responses/samples".to_owned());
+        let emails: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "eml"))
+            .filter_map(|path| std::fs::read(path).ok())
+            .map(encode)
+            .collect();
+        assert!(!emails.is_empty());
+
+        let start = std::time::Instant::now();
+        for raw in &emails {
+            parse_email(raw.clone()).unwrap();
+        }
+        let elapsed = start.elapsed();
+
+        println!(
+            "parsed {} emails in {elapsed:?}, {:?} per email",
+            emails.len(),
+            elapsed / emails.len() as u32
+        );
+    }
+
+    #[test]
+    fn enforces_parse_limits() {
+        let raw = encode(
+            "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/alternative; boundary=b\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+Hi\r
+--b\r
+Content-Type: text/html\r
+\r
+<div><p>Hi</p></div>\r
+--b--\r
+",
+        );
+        let parse = |limits: ParseLimits| {
+            parse_email_with_options(
+                raw.clone(),
+                ParseOptions {
+                    limits: Some(limits),
+                    ..Default::default()
+                },
+            )
+        };
+        let exceeded = |result: Return<Email>| matches!(result, Err(ParserError::LimitExceeded));
+
+        assert!(exceeded(parse(ParseLimits {
+            max_bytes: Some(64),
+            ..Default::default()
+        })));
+        assert!(exceeded(parse(ParseLimits {
+            max_parts: Some(2),
+            ..Default::default()
+        })));
+        assert!(exceeded(parse(ParseLimits {
+            max_html_nodes: Some(3),
+            ..Default::default()
+        })));
+        assert!(parse(ParseLimits {
+            max_bytes: Some(1024),
+            max_parts: Some(3),
+            max_html_nodes: Some(6),
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn counts_mime_parts_before_parsing() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/mixed; boundary=\"outer\"\r
+\r
+--outer\r
+Content-Type: multipart/alternative; boundary=inner\r
+\r
+--inner\r
+Content-Type: text/plain\r
+\r
+Hi\r
+-- \r
+Signature\r
+--inner\r
+Content-Type: text/html\r
+\r
+<p>Hi</p>\r
+--inner--\r
+--outer \r
+Content-Type: text/plain\r
+\r
+More\r
+--outer--\r
+";
+        assert_eq!(count_mime_parts(raw.as_bytes()), 5);
+        let parsed = MessageParser::default().parse(raw.as_bytes()).unwrap();
+        assert_eq!(parsed.parts.len(), 5);
+
+        assert_eq!(count_mime_parts(b"From: a@example.com\r\n\r\nHi\r\n"), 1);
+    }
+
+    #[test]
+    fn parses_group_addresses() {
+        let raw = "From: a@example.com\r
+To: Friends: a@x.com, Bob <b@x.com>;\r
+Cc: Undisclosed recipients:;\r
+\r
+Hi\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+
+        let to: Vec<(&str, Option<&str>)> = email
+            .to_addresses
+            .iter()
+            .map(|a| (a.address.as_str(), a.group.as_deref()))
+            .collect();
+        assert_eq!(
+            to,
+            [("a@x.com", Some("Friends")), ("b@x.com", Some("Friends"))]
+        );
+        assert_eq!(email.to.address, "a@x.com");
+
+        assert_eq!(email.cc_addresses.len(), 1);
+        assert!(email.cc_addresses[0].is_group);
+        assert_eq!(
+            email.cc_addresses[0].name.as_deref(),
+            Some("Undisclosed recipients")
+        );
+        assert_eq!(
+            display_name(&email.cc_addresses[0]),
+            "Undisclosed recipients"
+        );
+    }
+
+    #[test]
+    fn extracts_preheaders() {
+        let html = r#"<html><head><title>Deals</title></head><body>
+            <div style="display: none; max-height: 0px;">Up to 50% off this weekend&zwnj;&nbsp;&zwnj;&nbsp;</div>
+            <table><tr><td><h1>Big sale</h1></td></tr></table>
+        </body></html>"#;
+        assert_eq!(
+            extract_preheader(html).as_deref(),
+            Some("Up to 50% off this weekend")
+        );
+
+        let html =
+            r#"<body><span style="font-size:0;opacity:0">New arrivals</span><p>Hi</p></body>"#;
+        assert_eq!(extract_preheader(html).as_deref(), Some("New arrivals"));
+
+        let html = r#"<body><p>Hi</p><div style="display:none">Footer tracking</div></body>"#;
+        assert_eq!(extract_preheader(html), None);
+        assert_eq!(extract_preheader("<p>Hello</p>"), None);
+    }
+
+    #[test]
+    fn decodes_rfc2231_filenames() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/mixed; boundary=b\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+Hi\r
+--b\r
+Content-Type: application/pdf\r
+Content-Disposition: attachment;\r
+ filename*0*=UTF-8''Rapport%20annuel%20;\r
+ filename*1*=%C3%A9t%C3%A9%202024;\r
+ filename*2=.pdf\r
+\r
+%PDF\r
+--b\r
+Content-Type: text/plain; name*=iso-8859-1''caf%E9.txt\r
+Content-Disposition: attachment\r
+\r
+menu\r
+--b--\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        let names: Vec<Option<&str>> = email
+            .attachments
+            .iter()
+            .map(|a| a.filename.as_deref())
+            .collect();
+
+        assert_eq!(
+            names,
+            [Some("Rapport annuel été 2024.pdf"), Some("café.txt")]
+        );
+        assert_eq!(
+            email.attachments[0].content_type.as_deref(),
+            Some("application/pdf")
+        );
+
+        let attachments = parse_attachments_only(encode(raw)).unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[1].filename.as_deref(), Some("café.txt"));
+        assert_eq!(attachments[1].size, 4);
+    }
+
+    #[test]
+    fn detects_auto_replies() {
+        let auto_reply = |headers: &str| {
+            let raw = format!("From: a@example.com\r\nTo: b@example.com\r\n{headers}\r\nHi\r\n");
+            parse_email(encode(&raw)).unwrap().is_auto_reply
+        };
+
+        assert!(auto_reply("Auto-Submitted: auto-replied\r\n"));
+        assert!(auto_reply(
+            "Auto-Submitted: Auto-Replied; owner-email=\"a@example.com\"\r\n"
+        ));
+        assert!(auto_reply("X-Autoreply: yes\r\n"));
+        assert!(auto_reply("X-Autorespond: Out of office\r\n"));
+        assert!(!auto_reply("Auto-Submitted: no\r\n"));
+        assert!(!auto_reply("Auto-Submitted: auto-generated\r\n"));
+        assert!(!auto_reply(""));
+    }
+
+    #[test]
+    fn formats_addresses() {
+        let address = |name: Option<&str>, address: &str, group: Option<&str>| EmailAddress {
+            name: name.map(ToOwned::to_owned),
+            address: address.to_owned(),
+            is_group: false,
+            group: group.map(ToOwned::to_owned),
+        };
+
+        assert_eq!(
+            format_address(&address(Some("Jane Doe"), "jane@example.com", None)),
+            "Jane Doe <jane@example.com>"
+        );
+        assert_eq!(
+            format_address(&address(Some("Doe, \"JD\" Jane"), "jane@example.com", None)),
+            r#""Doe, \"JD\" Jane" <jane@example.com>"#
+        );
+        assert_eq!(
+            format_address(&address(Some("José"), "jose@example.com", None)),
+            "=?UTF-8?B?Sm9zw6k=?= <jose@example.com>"
+        );
+        assert_eq!(
+            format_address(&address(None, "a@example.com", None)),
+            "a@example.com"
+        );
+
+        let list = format_address_list(vec![
+            address(None, "a@x.com", Some("Friends")),
+            address(Some("Bob"), "b@x.com", Some("Friends")),
+            address(None, "c@x.com", None),
+            EmailAddress {
+                name: Some("Undisclosed recipients".to_owned()),
+                address: String::new(),
+                is_group: true,
+                group: None,
+            },
+        ]);
+        assert_eq!(
+            list,
+            "Friends: a@x.com, Bob <b@x.com>;, c@x.com, Undisclosed recipients:;"
+        );
+
+        // Round trips through the parser
+        let name = "Zoë Ångström, PhD — Département de recherche et développement";
+        let raw = format!(
+            "From: {}\r\nTo: b@example.com\r\n\r\nHi\r\n",
+            format_address(&address(Some(name), "zoe@example.com", None))
+        );
+        let email = parse_email(encode(&raw)).unwrap();
+        assert_eq!(email.from.name.as_deref(), Some(name));
+    }
+
+    #[test]
+    fn reads_dkim_domains() {
+        let email = |headers: &str| {
+            let raw =
+                format!("From: a@news.example.co.uk\r\nTo: b@example.com\r\n{headers}\r\nHi\r\n");
+            parse_email(encode(&raw)).unwrap()
+        };
+
+        let signed = email(
+            "DKIM-Signature: v=1; a=rsa-sha256; d=esp.net; s=s1;\r\n\tbh=abc=; b=def\r\n\
+             DKIM-Signature: v=1; a=rsa-sha256; d=\r\n Example.co.uk; s=s2; b=ghi\r\n",
+        );
+        assert_eq!(signed.dkim_domains, ["esp.net", "example.co.uk"]);
+        assert_eq!(signed.dkim_domain.as_deref(), Some("example.co.uk"));
+        assert_eq!(signed.dkim_aligned, Some(true));
+
+        let unaligned = email("DKIM-Signature: v=1; d=esp.net; s=s1; b=def\r\n");
+        assert_eq!(unaligned.dkim_domain.as_deref(), Some("esp.net"));
+        assert_eq!(unaligned.dkim_aligned, Some(false));
+
+        let unsigned = email("");
+        assert_eq!(unsigned.dkim_domain, None);
+        assert_eq!(unsigned.dkim_aligned, None);
+    }
+
+    #[test]
+    fn reads_bimi_location() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+BIMI-Location: v=BIMI1;\r
+ l=https://example.com/bimi/logo.svg;\r
+ a=https://example.com/bimi/vmc.pem\r
+\r
+Hi\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        assert_eq!(
+            email.bimi_logo_url.as_deref(),
+            Some("https://example.com/bimi/logo.svg")
+        );
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\n\r\nHi\r\n";
+        assert_eq!(parse_email(encode(raw)).unwrap().bimi_logo_url, None);
+    }
+
+    #[test]
+    fn detects_calendar_cancellations() {
+        let cancel_method = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+METHOD:CANCEL\r
+BEGIN:VEVENT\r
+UID:standup\r
+SEQUENCE:2\r
+DTSTART:20250101T090000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = parse_events(cancel_method).unwrap();
+        assert!(events[0].is_cancellation);
+
+        let cancelled_status = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+METHOD:REQUEST\r
+BEGIN:VEVENT\r
+UID:review\r
+STATUS:CANCELLED\r
+DTSTART:20250101T090000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:retro\r
+STATUS:CONFIRMED\r
+DTSTART:20250102T090000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = parse_events(cancelled_status).unwrap();
+        assert!(events[0].is_cancellation);
+        assert!(!events[1].is_cancellation);
+    }
+
+    #[test]
+    fn parses_mailing_list_headers() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+List-Id: \"Example announcements\" <announce.example.com>\r
+List-Post: NO\r
+List-Help: <mailto:help@example.com?subject=help>, <https://example.com/help>\r
+List-Archive: <https://example.com/archive>\r
+List-Unsubscribe: <https://example.com/unsubscribe>\r
+\r
+Hi\r
+";
+        let list = parse_email(encode(raw)).unwrap().mailing_list.unwrap();
+
+        assert_eq!(list.id.as_deref(), Some("announce.example.com"));
+        assert_eq!(list.name.as_deref(), Some("Example announcements"));
+        assert!(list.post.is_empty());
+        assert_eq!(
+            list.help,
+            [
+                "mailto:help@example.com?subject=help",
+                "https://example.com/help"
+            ]
+        );
+        assert_eq!(list.archive, ["https://example.com/archive"]);
+        assert_eq!(list.unsubscribe, ["https://example.com/unsubscribe"]);
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\n\r\nHi\r\n";
+        assert!(parse_email(encode(raw)).unwrap().mailing_list.is_none());
+    }
+
+    #[test]
+    fn normalizes_unicode() {
+        let decomposed = "Cafe\u{301} ﬁnal";
+        assert_eq!(
+            normalize_unicode(decomposed, NormalizationForm::Nfc),
+            "Café ﬁnal"
+        );
+        assert_eq!(
+            normalize_unicode(decomposed, NormalizationForm::Nfkc),
+            "Café final"
+        );
+        assert_eq!(
+            normalize_unicode("Café", NormalizationForm::Nfd),
+            "Cafe\u{301}"
+        );
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\nSubject: Re: =?UTF-8?Q?Cafe=CC=81?=\r\n\r\nCafe\u{301}\r\n";
+        let email = parse_email_with_options(
+            encode(raw),
+            ParseOptions {
+                subject_normalization: Some(NormalizationForm::Nfc),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(email.subject.as_deref(), Some("Re: Café"));
+        assert_eq!(email.subject_normalized.as_deref(), Some("Café"));
+        assert_eq!(email.text_bodies[0].text.trim(), "Cafe\u{301}");
+    }
+
+    #[test]
+    fn reports_charset_detection() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/alternative; boundary=b\r
+\r
+--b\r
+Content-Type: text/plain; charset=iso-8859-1\r
+Content-Transfer-Encoding: quoted-printable\r
+\r
+caf=E9\r
+--b\r
+Content-Type: text/plain; charset=x-unknown\r
+Content-Transfer-Encoding: quoted-printable\r
+\r
+caf=E9 =C3=A9t=C3=A9\r
+--b--\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        let declared = email
+            .text_bodies
+            .iter()
+            .find(|b| b.text.contains("café"))
+            .unwrap();
+        let detected = email
+            .text_bodies
+            .iter()
+            .find(|b| b.text.contains("été"))
+            .unwrap();
+
+        assert!(!declared.charset_detected);
+        assert_eq!(declared.charset_confidence, None);
+        assert!(detected.charset_detected);
+        assert!(detected.charset_confidence.unwrap() < 1.0);
+
+        assert_eq!(charset_confidence("café"), 1.0);
+        assert!(charset_confidence("cafÃ© Ã©tÃ©") < 0.8);
+    }
+
+    #[test]
+    fn parses_graph_batches() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\nSubject: Hi\r\n\r\nHello\r\n";
+        let body = serde_json::json!({
+            "responses": [
+                {
+                    "id": "1",
+                    "status": 200,
+                    "headers": { "Content-Type": "text/plain" },
+                    "body": STANDARD.encode(raw)
+                },
+                {
+                    "id": "2",
+                    "status": 404,
+                    "body": { "error": { "code": "ErrorItemNotFound", "message": "Not found" } }
+                }
+            ]
+        });
+        let mut sections = parse_graph_batch(body.to_string());
+        sections.sort_by(|a, b| a.batch_name.cmp(&b.batch_name));
+
+        assert_eq!(sections.len(), 2);
+        assert!(matches!(
+            &sections[0].response,
+            BatchResponse::Message(email) if email.subject.as_deref() == Some("Hi")
+        ));
+        assert!(matches!(
+            &sections[1].response,
+            BatchResponse::Error(error) if error.code == 404 && error.status == "ErrorItemNotFound"
+        ));
+    }
+
+    #[test]
+    fn extracts_brand_images() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/related; boundary=b\r
+\r
+--b\r
+Content-Type: text/html\r
+\r
+<img src=\"cid:pixel\" width=\"1\" height=\"1\">\r
+<img src=\"cid:logo\" width=\"120\" height=\"40\">\r
+<p>Hi</p><img src=\"cid:spacer\" width=\"8\">\r
+<img src=\"cid:banner\" width=\"200\" height=\"30\">\r
+--b\r
+Content-Type: image/png\r
+Content-ID: <logo>\r
+\r
+LOGO\r
+--b\r
+Content-Type: image/png\r
+Content-ID: <banner>\r
+\r
+BANNER\r
+--b\r
+Content-Type: image/gif\r
+Content-ID: <pixel>\r
+\r
+GIF\r
+--b--\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        assert!(email.attachments.iter().all(|a| a.data.is_empty()));
+        assert_eq!(extract_brand_image(&email), None);
+
+        let options = ParseOptions {
+            inline_part_data: true,
+            inline_attachments_below_bytes: Some(1024),
+            ..Default::default()
+        };
+        let email = parse_email_with_options(encode(raw), options).unwrap();
+        assert_eq!(extract_brand_image(&email).as_deref(), Some(&b"LOGO"[..]));
+        assert!(email.attachments.iter().all(|a| a.inline));
+        assert!(email.attachments.iter().all(|a| a.content_base64.is_none()));
+        assert_eq!(email.attachment_count, 0);
+
+        // Images without dimensions only compete by byte size among themselves
+        let photo = "P".repeat(6000);
+        let mixed = |width: &str| {
+            format!(
+                "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/related; boundary=b\r
+\r
+--b\r
+Content-Type: text/html\r
+\r
+<img src=\"cid:photo\"><img src=\"cid:logo\" {width}>\r
+--b\r
+Content-Type: image/jpeg\r
+Content-ID: <photo>\r
+\r
+{photo}\r
+--b\r
+Content-Type: image/png\r
+Content-ID: <logo>\r
+\r
+LOGO\r
+--b--\r
+"
+            )
+        };
+        let brand_image = |raw: String| {
+            let options = ParseOptions {
+                inline_part_data: true,
+                ..Default::default()
+            };
+            extract_brand_image(&parse_email_with_options(encode(raw), options).unwrap())
+        };
+        assert_eq!(
+            brand_image(mixed(r#"width="120" height="40""#)).as_deref(),
+            Some(&b"LOGO"[..])
+        );
+        assert_eq!(brand_image(mixed("")), Some(photo.into_bytes()));
+    }
+
+    #[test]
+    fn parses_addresses_strictly() {
+        let parsed =
+            parse_address_strict(r#""Doe, \"JD\" Jane" <jane.doe+news@mail.example.com>"#).unwrap();
+        assert_eq!(parsed.name.as_deref(), Some(r#"Doe, "JD" Jane"#));
+        assert_eq!(parsed.address, "jane.doe+news@mail.example.com");
+
+        let parsed = parse_address_strict("Jane Doe <jane@example.com>").unwrap();
+        assert_eq!(parsed.name.as_deref(), Some("Jane Doe"));
+        let parsed = parse_address_strict(r#""a <b" <x@example.com>"#).unwrap();
+        assert_eq!(parsed.name.as_deref(), Some("a <b"));
+        assert_eq!(parsed.address, "x@example.com");
+        assert!(parse_address_strict("jane@[192.0.2.1]").is_ok());
+        assert!(parse_address_strict("josé@exämple.de").is_ok());
+
+        let error = |input: &str| parse_address_strict(input).err().unwrap();
+        assert!(matches!(error("  "), AddressError::EmptyInput));
+        assert!(matches!(
+            error("jane doe@example.com"),
+            AddressError::ContainsWhitespace
+        ));
+        assert!(matches!(
+            error("Jane <jane@example.com"),
+            AddressError::UnbalancedBrackets
+        ));
+        assert!(matches!(
+            error("Doe, Jane <jane@example.com>"),
+            AddressError::InvalidDisplayName
+        ));
+        assert!(matches!(error("jane.example.com"), AddressError::MissingAt));
+        assert!(matches!(
+            error("@example.com"),
+            AddressError::MissingLocalPart
+        ));
+        assert!(matches!(error("jane@"), AddressError::MissingDomain));
+        assert!(matches!(
+            error("jane..doe@example.com"),
+            AddressError::InvalidLocalPart
+        ));
+        assert!(matches!(
+            error("jane@localhost"),
+            AddressError::InvalidDomain
+        ));
+        assert!(matches!(
+            error("jane@-example.com"),
+            AddressError::InvalidDomain
+        ));
+        assert!(matches!(
+            error(&format!("{}@example.com", "a".repeat(65))),
+            AddressError::TooLong
+        ));
+    }
+
+    #[test]
+    fn extracts_web_version_urls() {
+        let email = |html: &str| {
+            let raw = format!(
+                "From: a@example.com\r\nTo: b@example.com\r\nContent-Type: text/html\r\n\r\n{html}\r\n"
+            );
+            parse_email(encode(&raw)).unwrap()
+        };
+
+        let html = r#"<p>Trouble reading? <a href="https://example.com/web/1">View this email
+            in your browser</a></p><a href="https://example.com/read">Read online</a>"#;
+        assert_eq!(
+            extract_web_version_url(&email(html)).as_deref(),
+            Some("https://example.com/web/1")
+        );
+
+        let html = r#"<p><a href="https://example.de/w">Hier klicken</a>, um unseren Shop zu besuchen</p>"#;
+        assert_eq!(extract_web_version_url(&email(html)), None);
+
+        let html = r#"<p>Online-Version: <a href="https://example.de/w">hier</a></p>"#;
+        assert_eq!(
+            extract_web_version_url(&email(html)).as_deref(),
+            Some("https://example.de/w")
+        );
+        assert_eq!(extract_web_version_url(&email("<p>Hi</p>")), None);
+    }
+
+    #[test]
+    fn tracking_pixel_thresholds() {
+        let html = r#"
+            <img src="https://t.example.com/1x1" width="1" height="1">
+            <img src="https://t.example.com/2x2" width="2" height="2">
+            <img src="https://t.example.com/hidden" style="display: none" width="300">
+            <img src="https://t.example.com/zero" style="width:0;height:0">
+            <img src="https://example.com/images/spacer.gif?v=2" width="20">
+            <img src="data:image/gif;base64,R0lGODlhAQABAIAAAP///wAAACH5BAEAAAAALAAAAAABAAEAAAICRAEAOw==">
+            <img src="https://example.com/icon.png" width="12" height="12">
+            <img src="https://example.com/logo.png" width="120" height="40">
+        "#;
+
+        let pixels = detect_tracking_pixels(html);
+        assert_eq!(pixels.len(), 6);
+        assert!(pixels[..4]
+            .iter()
+            .all(|src| src.starts_with("https://t.example.com/")));
+        assert!(pixels[4].ends_with("spacer.gif?v=2"));
+        assert!(pixels[5].starts_with("data:image/gif"));
+        assert_eq!(detect_tracking_pixels_with_threshold(html, 1).len(), 5);
+        assert_eq!(detect_tracking_pixels_with_threshold(html, 16).len(), 7);
+    }
+
+    #[test]
+    fn counts_quote_levels() {
+        let text = "Sounds good\r\n> When works?\r\n> > >  Deep\r\n>>tight\r\n>\r\n2 > 1";
+        let line = |depth: u32, content: &str| QuotedLine {
+            depth,
+            content: content.to_owned(),
+        };
+
+        assert_eq!(
+            quote_levels(text),
+            [
+                line(0, "Sounds good"),
+                line(1, "When works?"),
+                line(3, " Deep"),
+                line(2, "tight"),
+                line(1, ""),
+                line(0, "2 > 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn collects_parse_diagnostics() {
+        let raw = encode(
+            "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/mixed; boundary=b\r
+\r
+--b\r
+Content-Type: text/html\r
+\r
+<script type=\"application/ld+json\">{\"@type\": \"Thing\"}</script>\r
+<script type=\"application/ld+json\">{\"@type\": </script>\r
+--b\r
+Content-Type: text/calendar; method=REQUEST\r
+\r
+BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:standup\r
+DTSTART;TZID=Mars/Olympus_Mons:20250101T090000\r
+END:VEVENT\r
+END:VCALENDAR\r
+--b--\r
+",
+        );
+
+        let email = parse_email(raw.clone()).unwrap();
+        assert!(email.warnings.is_empty());
+        assert_eq!(email.markups.len(), 1);
+
+        let options = ParseOptions {
+            parse_diagnostics: true,
+            ..Default::default()
+        };
+        let email = parse_email_with_options(raw, options).unwrap();
+        let messages: Vec<(u32, &str)> = email
+            .warnings
+            .iter()
+            .map(|warning| (warning.part, warning.message.as_str()))
+            .collect();
+        assert_eq!(messages.len(), 2, "{messages:?}");
+        assert_eq!(messages[0].0, 1);
+        assert!(messages[0]
+            .1
+            .starts_with("JSON-LD script #2 failed to parse"));
+        assert_eq!(
+            messages[1],
+            (
+                2,
+                "calendar part had unknown TZID Mars/Olympus_Mons in DTSTART"
+            )
+        );
+        assert_eq!(email.markups.len(), 1);
+    }
+
+    #[test]
+    fn extracts_sender_signals() {
+        let raw = "From: Example <No-Reply+billing@Example.com>\r
+To: b@example.com\r
+Authentication-Results: mx.example.net; dkim=pass header.i=@example.com;\r
+ spf=pass smtp.mailfrom=example.com; dmarc=pass (p=REJECT) header.from=example.com\r
+Authentication-Results: forged.example; dmarc=fail\r
+List-Unsubscribe: <https://example.com/unsubscribe>\r
+\r
+Hi\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        assert_eq!(
+            email.sender_signals,
+            SenderSignals {
+                authenticated: true,
+                bulk: true,
+                no_reply: true,
+                has_unsubscribe: true,
+                from_domain: Some("example.com".to_owned()),
+            }
+        );
+
+        let raw = "From: jane@example.com\r
+To: b@example.com\r
+Authentication-Results: mx.example.net; dkim=pass header.i=@other.example; dmarc=none\r
+Precedence: bulk\r
+\r
+Hi\r
+";
+        let signals = parse_email(encode(raw)).unwrap().sender_signals;
+        assert!(!signals.authenticated);
+        assert!(signals.bulk);
+        assert!(!signals.no_reply);
+        assert!(!signals.has_unsubscribe);
+    }
+
+    #[test]
+    fn decodes_html_body_entities() {
+        let raw = encode(
+            "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+\r
+<p>Caf&eacute; &amp; bar</p>\r
+",
+        );
+
+        let email = parse_email(raw.clone()).unwrap();
+        let body = &email.html_bodies[0];
+        assert!(body.text.starts_with("<p>Caf&eacute; &amp; bar</p>"));
+        assert!(body
+            .decoded_text
+            .as_deref()
+            .unwrap()
+            .starts_with("<p>Café & bar</p>"));
+
+        let options = ParseOptions {
+            decoded_text_strip_tags: true,
+            ..Default::default()
+        };
+        let email = parse_email_with_options(raw, options).unwrap();
+        assert_eq!(
+            email.html_bodies[0].decoded_text.as_deref().map(str::trim),
+            Some("Café & bar")
+        );
+    }
+
+    #[test]
+    fn normalizes_whitespace() {
+        assert_eq!(
+            normalize_whitespace("\r\n  Order \t #123 \r\n\r\n\r\n\n  Shipped\r  today  \n\n"),
+            "Order #123\n\nShipped today"
+        );
+
+        let items = extract_microdata(
+            r#"<div itemscope itemtype="https://schema.org/Order">
+                <span itemprop="orderNumber">
+                    123
+                </span>
+                <div itemprop="description">Two
+                    items,
+
+                    shipped</div>
+            </div>"#,
+        );
+        let properties = &items[0].properties;
+        assert_eq!(properties["orderNumber"], "123");
+        assert_eq!(properties["description"], "Two\nitems,\n\nshipped");
+    }
+
+    #[test]
+    fn reads_date_offset() {
+        let offset = |date: &str| {
+            let raw =
+                format!("From: a@example.com\r\nTo: b@example.com\r\nDate: {date}\r\n\r\nHi\r\n");
+            parse_email(encode(&raw)).unwrap().date_offset_seconds
+        };
+
+        assert_eq!(offset("Tue, 1 Jul 2025 09:00:00 +0530"), Some(19_800));
+        assert_eq!(offset("Tue, 1 Jul 2025 09:00:00 -0700"), Some(-25_200));
+        assert_eq!(offset("Tue, 1 Jul 2025 09:00:00 +0000"), Some(0));
+        assert_eq!(offset("Tue, 1 Jul 2025 09:00:00 -0000"), None);
+        assert_eq!(offset("not a date"), None);
+    }
+
+    #[test]
+    fn extracts_body_addresses() {
+        let raw = "From: Ann <ann@example.com>\r
+To: bob@example.com\r
+Cc: carol@example.com\r
+\r
+Hi Bob, please also reach out to Dana.Lee@Example.org and CAROL@example.com.\r
+Ping @dana@example.org on chat, or write to dana.lee@example.org,eve+ops@mail.example.net.\r
+Not these: me@localhost, a@b.c1, x..y@example.com\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+
+        assert_eq!(
+            extract_body_addresses(&email),
+            ["Dana.Lee@Example.org", "eve+ops@mail.example.net"]
+        );
+    }
+
+    #[test]
+    fn filters_json_ld_contexts() {
+        let html = r#"
+            <script type="application/ld+json">{"@context": "https://Schema.org/?lang=en#top", "@type": "Order"}</script>
+            <script type="application/ld+json">{"@context": "http://schema.org", "@type": "Order"}</script>
+            <script type="application/ld+json">[
+                {"@context": ["https://schema.org/", {"ex": "https://example.com/"}], "@type": "Order"},
+                {"@context": {"@vocab": "https://schema.org/"}, "@type": "Order"},
+                {"@context": "https://example.com/vocab", "@type": "Thing"}
+            ]</script>
+            <script type="application/ld+json">{"@context": "https://schema.org.example.com", "@type": "Thing"}</script>
+            <script type="application/ld+json">{"@type": "Thing"}</script>
+        "#;
+
+        assert_eq!(parse_json_lds(html, false).len(), 7);
+        let markups = parse_json_lds(html, true);
+        assert_eq!(markups.len(), 4);
+        assert!(markups
+            .iter()
+            .all(|markup| markup.contains(r#""@type":"Order""#)));
+    }
+
+    #[test]
+    fn extracts_primary_cta() {
+        let html = r##"
+            <a href="https://example.com/logo"><img src="logo.png"></a>
+            <a href="https://example.com/unsubscribe" style="background-color: #0057ff; padding: 4px">Unsubscribe</a>
+            <p>Thanks for your order. <a href="https://example.com/help">Get help</a></p>
+            <table><tr>
+                <td align="center" bgcolor="#0057ff" style="border-radius: 6px">
+                    <a href="https://example.com/confirm" style="padding: 12px 24px; color: #ffffff">
+                        Confirm your   order
+                    </a>
+                </td>
+            </tr></table>
+            <a class="btn-primary" href="https://example.com/track">Track package</a>
+        "##;
+        assert_eq!(
+            extract_primary_cta(html),
+            Some(Cta {
+                text: "Confirm your order".to_owned(),
+                href: "https://example.com/confirm".to_owned(),
+            })
+        );
+
+        let html = r#"<a href="https://example.com/a" style="background: #fff; padding: 8px">Read more</a>"#;
+        assert_eq!(extract_primary_cta(html), None);
+    }
+
+    #[test]
+    fn records_body_part_ids() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/mixed; boundary=outer\r
+\r
+--outer\r
+Content-Type: multipart/alternative; boundary=inner\r
+\r
+--inner\r
+Content-Type: text/plain\r
+\r
+Hi\r
+--inner\r
+Content-Type: text/HTML; charset=utf-8\r
+\r
+<p>Hi</p>\r
+--inner--\r
+--outer\r
+Content-Type: application/pdf\r
+Content-Disposition: attachment; filename=a.pdf\r
+\r
+%PDF\r
+--outer--\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        assert_eq!(email.text_bodies[0].part_id, "1.1");
+        assert_eq!(email.text_bodies[0].content_type, "text/plain");
+        assert_eq!(email.html_bodies[0].part_id, "1.2");
+        assert_eq!(email.html_bodies[0].content_type, "text/html");
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\n\r\nHi\r\n";
+        let email = parse_email(encode(raw)).unwrap();
+        assert_eq!(email.text_bodies[0].part_id, "1");
+        assert_eq!(email.text_bodies[0].content_type, "text/plain");
+    }
+
+    #[test]
+    fn builds_previews() {
+        let preview = |raw: &str| parse_email(encode(raw)).unwrap().preview;
+
+        assert_eq!(
+            preview(
+                "From: a@example.com\r\nTo: b@example.com\r\n\r\n\r\n  Hi   there,\r\nsee you\r\n"
+            ),
+            Some("Hi there, see you".to_owned())
+        );
+        assert_eq!(
+            preview(
+                "From: a@example.com\r\nTo: b@example.com\r\nContent-Type: text/html\r\n\r\n<style>p {}</style><p>Hi &amp; bye</p>\r\n"
+            ),
+            Some("Hi & bye".to_owned())
+        );
+
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/mixed; boundary=b\r
+\r
+--b\r
+Content-Type: text/calendar; method=REQUEST\r
+\r
+BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup\r
+DTSTART:20250101T090000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+--b--\r
+";
+        assert_eq!(preview(raw), Some("Calendar invite: Standup".to_owned()));
+
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/mixed; boundary=b\r
+\r
+--b\r
+Content-Type: application/pdf\r
+Content-Disposition: attachment; filename=invoice.pdf\r
+\r
+%PDF\r
+--b--\r
+";
+        assert_eq!(preview(raw), Some("Attachment: invoice.pdf".to_owned()));
+    }
+
+    #[test]
+    fn finds_conference_links() {
+        let calendar = |properties: &str| {
+            let ics = format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:1\r\n{properties}END:VEVENT\r\nEND:VCALENDAR\r\n"
+            );
+            parse_events(&ics).unwrap().remove(0).conference_link
+        };
+
+        assert_eq!(
+            calendar("LOCATION:https://acme.zoom.us/j/123456789?pwd=abc\r\n"),
+            Some("https://acme.zoom.us/j/123456789?pwd=abc".to_owned())
+        );
+        assert_eq!(
+            calendar(
+                "DESCRIPTION:Join: <https://teams.microsoft.com/l/meetup-join/19%3ameeting_x/0?context=%7b%7d>\\, thanks\r\n"
+            ),
+            Some("https://teams.microsoft.com/l/meetup-join/19%3ameeting_x/0?context=%7b%7d".to_owned())
+        );
+        assert_eq!(
+            calendar("X-MICROSOFT-SKYPETEAMSMEETINGURL:https://acme.webex.com/meet/jane\r\nLOCATION:Room 4\r\n"),
+            Some("https://acme.webex.com/meet/jane".to_owned())
+        );
+        assert_eq!(
+            calendar("X-GOOGLE-CONFERENCE:https://meet.google.com/abc-defg-hij\r\n"),
+            Some("https://meet.google.com/abc-defg-hij".to_owned())
+        );
+        assert_eq!(
+            calendar("LOCATION:https://example.com/zoom.us/j/1\r\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn builds_thread_keys() {
+        let key = |subject: &str| {
+            let raw = format!(
+                "From: a@example.com\r\nTo: b@example.com\r\nSubject: {subject}\r\n\r\nHi\r\n"
+            );
+            thread_key(&parse_email(encode(&raw)).unwrap())
+        };
+
+        assert_eq!(key("Quarterly   Report"), "quarterly report");
+        assert_eq!(key("RE: Fwd: quarterly report"), key("Quarterly Report"));
+        assert_eq!(key("AW: Straße"), "straße");
+        assert_eq!(
+            thread_key(
+                &parse_email(encode(
+                    "From: a@example.com\r\nTo: b@example.com\r\n\r\nHi\r\n"
+                ))
+                .unwrap()
+            ),
+            ""
+        );
+    }
+
+    #[test]
+    fn inlines_small_attachment_contents() {
+        let raw = encode(
+            "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/mixed; boundary=b\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+Hi\r
+--b\r
+Content-Type: text/vcard\r
+Content-Disposition: attachment; filename=jane.vcf\r
+\r
+BEGIN:VCARD\r
+--b\r
+Content-Type: application/pdf\r
+Content-Disposition: attachment; filename=report.pdf\r
+Content-Transfer-Encoding: base64\r
+\r
+JVBERi0xLjQKJeLjz9MKMSAwIG9iago8PC9UeXBlL0NhdGFsb2c+PgplbmRvYmoK\r
+--b--\r
+",
+        );
+
+        let email = parse_email(raw.clone()).unwrap();
+        assert!(email.attachments.iter().all(|a| a.content_base64.is_none()));
+
+        let options = ParseOptions {
+            inline_attachments_below_bytes: Some(32),
+            ..Default::default()
+        };
+        let email = parse_email_with_options(raw, options).unwrap();
+        assert_eq!(
+            email.attachments[0].content_base64.as_deref(),
+            Some("QkVHSU46VkNBUkQ=")
+        );
+        assert_eq!(email.attachments[1].content_base64, None);
+    }
+
+    #[test]
+    fn compares_canonical_addresses() {
+        assert_eq!(
+            canonical_address(" <John.Doe+x@GoogleMail.com> "),
+            "johndoe@gmail.com"
+        );
+        assert_eq!(
+            canonical_address("Jane.Doe+news@Example.com"),
+            "jane.doe@example.com"
+        );
+        assert_eq!(canonical_address("not an address"), "not an address");
+
+        assert!(addresses_equal("John.Doe+x@GMAIL.com", "johndoe@gmail.com"));
+        assert!(addresses_equal("jane@example.com", "JANE+work@example.com"));
+        assert!(!addresses_equal(
+            "jane.doe@example.com",
+            "janedoe@example.com"
+        ));
+        assert!(!addresses_equal("jane@example.com", "jane@example.org"));
+    }
+
+    #[test]
+    fn keeps_only_visible_bodies() {
+        let raw = encode(
+            "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/alternative; boundary=b\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+Sounds good!\r
+\r
+On Mon, Jan 6, 2025 at 9:00 AM Bob <b@example.com> wrote:\r
+> Lunch?\r
+--b\r
+Content-Type: text/html\r
+\r
+<div>Sounds good!</div><div class=\"gmail_quote gmail_quote_container\">Lunch?</div>\r
+--b--\r
+",
+        );
+        let options = ParseOptions {
+            visible_only: true,
+            ..Default::default()
+        };
+        let email = parse_email_with_options(raw.clone(), options).unwrap();
+
+        assert_eq!(email.text_bodies[0].text, "Sounds good!");
+        assert!(!email.html_bodies[0].text.contains("Lunch?"));
+        assert!(email.html_bodies[0].text.contains("Sounds good!"));
+
+        let email = parse_email(raw).unwrap();
+        assert!(email.text_bodies[0].text.contains("Lunch?"));
+        assert!(email.html_bodies[0].text.contains("Lunch?"));
+
+        let raw = encode("From: a@example.com\r\nTo: b@example.com\r\n\r\nNo quote here\r\n");
+        let options = ParseOptions {
+            visible_only: true,
+            ..Default::default()
+        };
+        let email = parse_email_with_options(raw, options).unwrap();
+        assert_eq!(email.text_bodies[0].text.trim(), "No quote here");
+    }
+
+    #[test]
+    fn falls_back_to_body_unsubscribe_links() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+\r
+<p>Don't want these? <a href=\"https://example.com/prefs\">Click here</a></p>\r
+<p><a href=\"https://example.com/unsubscribe?u=1\">Unsubscribe</a></p>\r
+";
+        let unsubscribe = parse_email(encode(raw)).unwrap().unsubscribe;
+        assert_eq!(
+            unsubscribe.get.as_deref(),
+            Some("https://example.com/unsubscribe?u=1")
+        );
+        assert_eq!(unsubscribe.source, Some(UnsubscribeSource::Body));
+
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+List-Unsubscribe: <https://example.com/one-click>\r
+Content-Type: text/html\r
+\r
+<a href=\"https://example.com/unsubscribe\">Unsubscribe</a>\r
+";
+        let unsubscribe = parse_email(encode(raw)).unwrap().unsubscribe;
+        assert_eq!(
+            unsubscribe.get.as_deref(),
+            Some("https://example.com/one-click")
+        );
+        assert_eq!(
+            unsubscribe.website.as_deref(),
+            Some("https://example.com/unsubscribe")
+        );
+        assert_eq!(unsubscribe.source, Some(UnsubscribeSource::Header));
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\n\r\nHi\r\n";
+        let unsubscribe = parse_email(encode(raw)).unwrap().unsubscribe;
+        assert_eq!(unsubscribe.get, None);
+        assert_eq!(unsubscribe.source, None);
+    }
+
+    #[test]
+    fn parses_multiple_authors() {
+        let raw = "From: Ann <ann@example.com>, Bob <bob@example.com>\r
+Sender: BOB@example.com\r
+To: c@example.com\r
+\r
+Hi\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        assert!(email.from_is_multiple);
+        assert_eq!(email.from_addresses.len(), 2);
+        assert_eq!(email.from.address, "bob@example.com");
+        assert_eq!(email.from.name.as_deref(), Some("Bob"));
+        assert_eq!(
+            email.from.text,
+            "Ann <ann@example.com>, Bob <bob@example.com>"
+        );
+
+        let raw =
+            "From: Ann <ann@example.com>, Bob <bob@example.com>\r\nTo: c@example.com\r\n\r\nHi\r\n";
+        let email = parse_email(encode(raw)).unwrap();
+        assert!(email.from_is_multiple);
+        assert_eq!(email.from.address, "ann@example.com");
+
+        let raw = "From: Ann <ann@example.com>\r\nTo: c@example.com\r\n\r\nHi\r\n";
+        assert!(!parse_email(encode(raw)).unwrap().from_is_multiple);
+    }
+
+    #[test]
+    fn summarizes_image_alt_text() {
+        let html = r#"
+            <img src="https://example.com/logo.png" alt="Logo">
+            <img src="https://example.com/hero.jpg" alt="  Summer   sale: 50% off ">
+            <img src="https://example.com/hero2.jpg" alt="Summer sale: 50% off">
+            <img src="https://example.com/divider.png" alt="">
+            <img src="https://example.com/banner_2025.jpg" alt="banner_2025.jpg">
+            <img src="https://example.com/open.gif" width="1" height="1" alt="Open tracker">
+            <img src="https://example.com/shoes.jpg" alt="Shop new shoes">
+        "#;
+        assert_eq!(
+            extract_alt_text(html).as_deref(),
+            Some("Summer sale: 50% off\nShop new shoes")
+        );
+        assert_eq!(extract_alt_text(r#"<img src="a.png" alt=" ">"#), None);
+
+        let raw = format!(
+            "From: a@example.com\r\nTo: b@example.com\r\nContent-Type: text/html\r\n\r\n{}\r\n",
+            html.replace('\n', "\r\n")
+        );
+        let options = || ParseOptions {
+            alt_text_summary: true,
+            ..Default::default()
+        };
+        assert_eq!(parse_email(encode(&raw)).unwrap().alt_text_summary, None);
+        let email = parse_email_with_options(encode(&raw), options()).unwrap();
+        assert_eq!(
+            email.alt_text_summary.as_deref(),
+            Some("Summer sale: 50% off\nShop new shoes")
+        );
+
+        let raw = format!(
+            "From: a@example.com\r\nTo: b@example.com\r\nContent-Type: text/html\r\n\r\n<p>{}</p>{}\r\n",
+            "Plenty of text to read. ".repeat(10),
+            r#"<img src="https://example.com/a.jpg" alt="Shop new shoes">"#
+        );
+        assert_eq!(
+            parse_email_with_options(encode(&raw), options())
+                .unwrap()
+                .alt_text_summary,
+            None
+        );
+    }
+
+    #[test]
+    fn extracts_data_uris() {
+        let html = r#"
+            <img src="https://example.com/a.png">
+            <img src="data:image/png;base64,iVBO
+                Rw0K">
+            <img src="DATA:image/svg+xml;charset=utf-8,%3Csvg%2F%3E">
+            <img src="data:,Hello%20world">
+            <img src="data:image/gif;base64,not base64!">
+            <img src="data:image/png;base64">
+        "#;
+
+        assert_eq!(
+            extract_data_uris(html),
+            [
+                DataUri {
+                    mime_type: "image/png".to_owned(),
+                    bytes: vec![0x89, b'P', b'N', b'G', b'\r', b'\n'],
+                },
+                DataUri {
+                    mime_type: "image/svg+xml".to_owned(),
+                    bytes: b"<svg/>".to_vec(),
+                },
+                DataUri {
+                    mime_type: "text/plain".to_owned(),
+                    bytes: b"Hello world".to_vec(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_content_language() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Language: en-US, fr\r
+Content-Type: multipart/alternative; boundary=b\r
+\r
+--b\r
+Content-Type: text/plain\r
+Content-Language: de\r
+\r
+Hallo\r
+--b\r
+Content-Type: text/html\r
+\r
+<p>Hello</p>\r
+--b--\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+        assert_eq!(email.content_language.as_deref(), Some("en-US"));
+        assert_eq!(email.text_bodies[0].content_language.as_deref(), Some("de"));
+        assert_eq!(email.html_bodies[0].content_language, None);
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\n\r\nHi\r\n";
+        assert_eq!(parse_email(encode(raw)).unwrap().content_language, None);
+    }
+
+    #[test]
+    fn batch_response_progress() {
+        use std::sync::{Arc, Mutex};
+
+        struct Recorder(Arc<Mutex<Vec<(u32, u32)>>>);
+
+        impl BatchProgress for Recorder {
+            fn on_section(&self, index: u32, total: u32) {
+                self.0.lock().unwrap().push((index, total));
+            }
+        }
+
+        let body = "--batch_a\r\nContent-Type: application/http\r\n\r\nHTTP/1.1 404 Not Found\r\n\r\n{\"code\": 404, \"message\": \"Not Found\", \"status\": \"NOT_FOUND\", \"errors\": []}\r\n--batch_a--\r\n";
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let sections =
+            parse_batch_response_with_progress(body.to_owned(), Box::new(Recorder(calls.clone())));
+
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].batch_name, "batch_a");
+
+        let mut calls = calls.lock().unwrap().clone();
+        calls.sort();
+        let total = body.split("--").count() as u32;
+        assert_eq!(
+            calls,
+            (0..total).map(|index| (index, total)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn sender_identity_prefers_organizations() {
+        let raw = r#"From: news@acme.example
+To: b@example.com
+Content-Type: text/html
+
+<html><head>
+<script type="application/ld+json">{"@context": "https://schema.org", "@type": "Person", "name": "Ada"}</script>
+<script type="application/ld+json">{"@context": "https://schema.org", "@type": "Organization", "name": "Acme", "sameAs": "https://x.com/acme"}</script>
+</head><body>Hi</body></html>
+"#;
+        let email = parse_email(encode(raw)).unwrap();
+        let identity = extract_sender_identity(&email).unwrap();
+        assert!(is_organization(&identity));
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\n\r\nHi\r\n";
+        assert!(extract_sender_identity(&parse_email(encode(raw)).unwrap()).is_none());
+    }
+
+    #[test]
+    fn best_text_prefers_plain_text() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/alternative; boundary=b\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+Hello   there,\r
+\r
+\r
+See you\r
+--b\r
+Content-Type: text/html\r
+\r
+<p>Hello from HTML</p>\r
+--b--\r
+";
+        assert_eq!(
+            best_text(&parse_email(encode(raw)).unwrap()),
+            "Hello there,\n\nSee you"
+        );
+
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+\r
+<p>Hello <b>from</b> HTML</p>\r
+";
+        assert_eq!(
+            best_text(&parse_email(encode(raw)).unwrap()),
+            "Hello from HTML"
+        );
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\n\r\n\r\n";
+        assert_eq!(best_text(&parse_email(encode(raw)).unwrap()), "");
+    }
+
+    #[test]
+    fn separates_unsubscribe_and_preferences_links() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+List-Unsubscribe: <https://example.com/one-click>\r
+Content-Type: text/html\r
+\r
+<p>Update your email preferences <a href=\"https://example.com/prefs\">here</a>.</p>\r
+<p><a href=\"https://example.com/bye\">Unsubscribe</a> | <a href=\"https://example.com/settings\">Email settings</a></p>\r
+";
+        let unsubscribe = parse_email(encode(raw)).unwrap().unsubscribe;
+        assert_eq!(
+            unsubscribe.unsubscribe_url.as_deref(),
+            Some("https://example.com/bye")
+        );
+        assert_eq!(
+            unsubscribe.manage_preferences_url.as_deref(),
+            Some("https://example.com/settings")
+        );
+        assert_eq!(
+            unsubscribe.website.as_deref(),
+            Some("https://example.com/bye")
+        );
+
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+\r
+<p>Manage preferences <a href=\"https://example.com/prefs\">here</a></p>\r
+";
+        let unsubscribe = parse_email(encode(raw)).unwrap().unsubscribe;
+        assert_eq!(unsubscribe.unsubscribe_url, None);
+        assert_eq!(
+            unsubscribe.manage_preferences_url.as_deref(),
+            Some("https://example.com/prefs")
+        );
+        assert_eq!(unsubscribe.get, None);
+        assert_eq!(unsubscribe.source, Some(UnsubscribeSource::Body));
+    }
+
+    #[test]
+    fn blocks_remote_content() {
+        let html = r#"<html><head>
+<link rel="stylesheet" href="https://example.com/mail.css">
+<style>@import "https://example.com/fonts.css"; .hero { background: url('https://example.com/hero.png') no-repeat; }</style>
+</head><body background="//example.com/bg.gif">
+<img src="https://track.example.com/open.gif" width="1" height="1">
+<img src="cid:logo@example.com" alt="Logo">
+<img src="data:image/png;base64,iVBORw0KGgo=">
+<div style="background-image: url(http://example.com/banner.jpg); color: red">Sale</div>
+</body></html>"#;
+        let blocked = block_remote_content(html.to_owned()).unwrap();
+
+        assert_eq!(blocked.blocked_count, 6);
+        assert!(!blocked.html.contains(r#" src="https://"#));
+        assert!(blocked
+            .html
+            .contains(r#"data-blocked-src="https://track.example.com/open.gif""#));
+        assert!(blocked.html.contains(r#"src="cid:logo@example.com""#));
+        assert!(blocked
+            .html
+            .contains(r#"src="data:image/png;base64,iVBORw0KGgo=""#));
+        assert!(blocked
+            .html
+            .contains(r#"data-blocked-background="//example.com/bg.gif""#));
+        assert!(blocked
+            .html
+            .contains(r#"data-blocked-href="https://example.com/mail.css""#));
+        assert!(blocked
+            .html
+            .contains("style=\"background-image: none; color: red\""));
+        assert!(blocked
+            .html
+            .contains("@import none; .hero { background: none no-repeat; }"));
+        assert!(blocked.html.ends_with("</body></html>"));
+
+        let plain = block_remote_content("<p>Hi</p>".to_owned()).unwrap();
+        assert_eq!(plain.html, "<p>Hi</p>");
+        assert_eq!(plain.blocked_count, 0);
+    }
+
+    #[test]
+    fn snippets_cut_on_word_boundaries() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Subject: Weekly update\r
+Content-Type: text/plain; charset=utf-8\r
+\r
+Caf\u{e9} r\u{e9}sum\u{e9}   is ready\r
+for review\r
+";
+        let email = parse_email(encode(raw)).unwrap();
+
+        assert_eq!(snippet(&email, 120), "Café résumé is ready for review");
+        assert_eq!(snippet(&email, 16), "Café résumé is…");
+        assert_eq!(snippet(&email, 4), "Caf…");
+        assert_eq!(snippet(&email, 0), "");
+
+        let raw =
+            "From: a@example.com\r\nTo: b@example.com\r\nSubject: Only  a subject\r\n\r\n\r\n";
+        assert_eq!(
+            snippet(&parse_email(encode(raw)).unwrap(), 120),
+            "Only a subject"
+        );
+    }
+
+    #[test]
+    fn detects_message_security() {
+        let security = |raw: &str| parse_email(encode(raw)).unwrap().security;
+
+        let signed = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/signed; protocol=\"application/pkcs7-signature\"; micalg=sha-256; boundary=b\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+Signed hello\r
+--b\r
+Content-Type: application/pkcs7-signature; name=smime.p7s\r
+Content-Disposition: attachment; filename=smime.p7s\r
+Content-Transfer-Encoding: base64\r
+\r
+MIAGCSqGSIb3DQEHAqCAMIACAQEx\r
+--b--\r
+";
+        assert_eq!(security(signed), Some(MessageSecurity::Signed));
+
+        let encrypted = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: application/pkcs7-mime; smime-type=enveloped-data; name=smime.p7m\r
+Content-Disposition: attachment; filename=smime.p7m\r
+Content-Transfer-Encoding: base64\r
+\r
+MIAGCSqGSIb3DQEHA6CAMIACAQAx\r
+";
+        let email = parse_email(encode(encrypted)).unwrap();
+        assert_eq!(email.security, Some(MessageSecurity::Encrypted));
+        assert_eq!(email.attachments.len(), 1);
+
+        let pgp = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=b\r
+\r
+--b\r
+Content-Type: application/pgp-encrypted\r
+\r
+Version: 1\r
+--b\r
+Content-Type: application/octet-stream; name=encrypted.asc\r
+\r
+-----BEGIN PGP MESSAGE-----\r
+hQEMA2k3\r
+-----END PGP MESSAGE-----\r
+--b--\r
+";
+        assert_eq!(security(pgp), Some(MessageSecurity::Encrypted));
+
+        let inline = "From: a@example.com\r
+To: b@example.com\r
+\r
+-----BEGIN PGP SIGNED MESSAGE-----\r
+Hash: SHA256\r
+\r
+Hello\r
+-----BEGIN PGP SIGNATURE-----\r
+iQEz\r
+-----END PGP SIGNATURE-----\r
+";
+        assert_eq!(security(inline), Some(MessageSecurity::Signed));
+
+        let raw = "From: a@example.com\r\nTo: b@example.com\r\n\r\nHi\r\n";
+        assert_eq!(security(raw), None);
+    }
+
+    #[test]
+    fn calendar_event_coordinates() {
+        let ics = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:geo\r
+LOCATION:Apple Park\r
+GEO:37.334900;-122.009020\r
+DTSTART:20250101T090000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:bad-geo\r
+GEO:137.3;-122.0\r
+DTSTART:20250101T090000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = parse_events(ics).unwrap();
+        assert_eq!(events[0].location.as_deref(), Some("Apple Park"));
+        assert_eq!(events[0].latitude, Some(37.3349));
+        assert_eq!(events[0].longitude, Some(-122.00902));
+        assert_eq!(events[1].latitude, None);
+
+        let markup = serde_json::json!({
+            "@type": "Event",
+            "name": "Launch",
+            "startDate": "2025-01-01T09:00:00Z",
+            "location": {
+                "@type": "Place",
+                "name": "Moscone Center",
+                "geo": { "@type": "GeoCoordinates", "latitude": "37.784", "longitude": -122.401 }
+            }
+        });
+        let event = markup_calendar_event(&markup).unwrap();
+        assert_eq!(event.location.as_deref(), Some("Moscone Center"));
+        assert_eq!(event.latitude, Some(37.784));
+        assert_eq!(event.longitude, Some(-122.401));
+    }
+
+    #[test]
+    fn calendar_end_from_duration() {
+        let ics = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:duration\r
+DTSTART:20250101T090000Z\r
+DURATION:PT1H30M\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:both\r
+DTSTART:20250101T090000Z\r
+DTEND:20250101T100000Z\r
+DURATION:PT5H\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let events = parse_events(ics).unwrap();
+        assert_eq!(events[0].start, Some(1_735_722_000_000));
+        assert_eq!(events[0].end, Some(1_735_722_000_000 + 90 * 60 * 1000));
+        assert_eq!(events[1].end, Some(1_735_725_600_000));
+
+        assert_eq!(parse_ics_duration("P1W"), Some(7 * 24 * 3_600_000));
+        assert_eq!(parse_ics_duration("-PT15M"), Some(-15 * 60_000));
+        assert_eq!(parse_ics_duration("P1DT2H3M4S"), Some(93_784_000));
+        assert_eq!(parse_ics_duration("P"), None);
+        assert_eq!(parse_ics_duration("PT1H30"), None);
+        assert_eq!(parse_ics_duration("P1M"), None);
+    }
+
+    #[test]
+    fn body_unsubscribe_get_needs_unsubscribe_text() {
+        let raw = "From: a@example.com\r
+To: b@example.com\r
+Content-Type: text/html\r
+\r
+<p>Don't want these? Unsubscribe below. <a href=\"https://example.com/shop\">Shop now</a></p>\r
+";
+        let unsubscribe = parse_email(encode(raw)).unwrap().unsubscribe;
+        assert_eq!(unsubscribe.get, None);
+        assert_eq!(unsubscribe.unsubscribe_url, None);
+        assert_eq!(
+            unsubscribe.website.as_deref(),
+            Some("https://example.com/shop")
+        );
+    }
+
+    #[test]
+    fn organizational_domains() {
+        assert_eq!(organizational_domain("mail.web.de"), "web.de");
+        assert_eq!(organizational_domain("news.gmx.de"), "gmx.de");
+        assert_eq!(
+            organizational_domain("Mail.Example.co.uk."),
+            "example.co.uk"
+        );
+        assert_eq!(organizational_domain("alice.github.io"), "alice.github.io");
+        assert_ne!(
+            organizational_domain("alice.github.io"),
+            organizational_domain("mallory.github.io")
+        );
+        assert_eq!(organizational_domain("localhost"), "localhost");
+
+        assert_eq!(registrable_label("mail.web.de"), "web");
+        assert_eq!(registrable_label("shop.co.uk"), "shop");
+        assert_eq!(registrable_label("co.uk"), "co");
     }
 }
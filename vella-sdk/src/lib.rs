@@ -4,5 +4,8 @@ uniffi::setup_scaffolding!();
 mod email;
 pub use email::*;
 
+mod schemaorg;
+pub use schemaorg::*;
+
 mod tokenizers;
 pub use tokenizers::*;
@@ -0,0 +1,1078 @@
+use serde::{Deserialize, Deserializer};
+use serde_with::{serde_as, DeserializeAs, OneOrMany};
+
+use crate::email::parse_schema_date;
+
+/// Checks the `@type` of a schema.org object, accepting objects without one as
+/// the type is often implied by the property holding them.
+fn has_type(typ: &Option<String>, expected: &str) -> bool {
+    typ.as_deref().is_none_or(|typ| {
+        typ.trim_start_matches("http://schema.org/")
+            .trim_start_matches("https://schema.org/")
+            == expected
+    })
+}
+
+/// A schema.org `Language`, given either as an object or as a bare language code.
+#[derive(uniffi::Record, Deserialize, Debug, PartialEq)]
+#[serde(from = "LanguageIn")]
+struct Language {
+    name: Option<String>,
+    /// IETF BCP 47 code, taken from `alternateName` when given as an object
+    code: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LanguageIn {
+    Code(String),
+    Object {
+        name: Option<String>,
+        #[serde(rename = "alternateName")]
+        alternate_name: Option<String>,
+    },
+}
+
+impl From<LanguageIn> for Language {
+    fn from(value: LanguageIn) -> Self {
+        match value {
+            LanguageIn::Code(code) => Self {
+                name: None,
+                code: Some(code),
+            },
+            LanguageIn::Object {
+                name,
+                alternate_name,
+            } => Self {
+                name,
+                code: alternate_name,
+            },
+        }
+    }
+}
+
+/// https://schema.org/PronounceableText
+#[serde_as]
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PronounceableText {
+    #[serde(rename = "@type")]
+    typ: Option<String>,
+    text_value: Option<String>,
+    /// Pronunciation in the notation given by `speech_to_text_markup`, e.g. IPA
+    phonetic_text: Option<String>,
+    speech_to_text_markup: Option<String>,
+    #[serde_as(as = "OneOrMany<_>")]
+    #[serde(default)]
+    in_language: Vec<Language>,
+}
+
+/// Parses a JSON-LD `PronounceableText`, returning `None` for other types.
+#[uniffi::export]
+fn parse_pronounceable_text(json: &str) -> Option<PronounceableText> {
+    serde_json::from_str::<PronounceableText>(json)
+        .ok()
+        .filter(|text| has_type(&text.typ, "PronounceableText"))
+}
+
+/// A `URL`, or an object such as an `ImageObject` holding one in `url`.
+struct UrlOrObject;
+
+impl<'de> DeserializeAs<'de, String> for UrlOrObject {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Url(String),
+            Object { url: String },
+        }
+
+        Value::deserialize(deserializer).map(|value| match value {
+            Value::Url(url) | Value::Object { url } => url,
+        })
+    }
+}
+
+/// A `Text`, or an object such as a `Brand` or `Organization` holding one in `name`.
+struct NameOrObject;
+
+impl<'de> DeserializeAs<'de, String> for NameOrObject {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Name(String),
+            Object { name: String },
+        }
+
+        Value::deserialize(deserializer).map(|value| match value {
+            Value::Name(name) | Value::Object { name } => name,
+        })
+    }
+}
+
+/// A `Number`, which schema.org also allows as a string like `"19.99"`. Values
+/// that aren't a number either way are read as `None` instead of failing.
+struct LenientNumber;
+
+/// Reads a number written with either `.` or `,` as the decimal separator, like
+/// `"1,299.00"`, `"1.299,00"` or `"19,99"`. A lone separator is only a decimal one
+/// when 1 or 2 digits follow it, and thousands separators only count between groups
+/// of 3 digits. Others, like `"1,299"`, are ambiguous and read as `None`.
+fn parse_lenient_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", text),
+    };
+    let is_grouped = |integer: &str, separator: char| {
+        let mut groups = integer.split(separator);
+        let first = groups.next().unwrap_or_default();
+        (1..=3).contains(&first.len()) && groups.all(|group| group.len() == 3)
+    };
+    let is_fraction = |fraction: &str| (1..=2).contains(&fraction.len());
+
+    let (integer, fraction) = match (digits.rfind('.'), digits.rfind(',')) {
+        (None, None) => (digits.to_owned(), ""),
+        // `1,299.00`
+        (Some(dot), Some(comma)) if comma < dot && is_grouped(&digits[..dot], ',') => {
+            (digits[..dot].replace(',', ""), &digits[dot + 1..])
+        }
+        // `1.299,00`
+        (Some(dot), Some(comma)) if dot < comma && is_grouped(&digits[..comma], '.') => {
+            (digits[..comma].replace('.', ""), &digits[comma + 1..])
+        }
+        (Some(_), Some(_)) => return None,
+        (Some(dot), None) if digits.matches('.').count() == 1 => {
+            (digits[..dot].to_owned(), &digits[dot + 1..])
+        }
+        (None, Some(comma))
+            if digits.matches(',').count() == 1 && is_fraction(&digits[comma + 1..]) =>
+        {
+            (digits[..comma].to_owned(), &digits[comma + 1..])
+        }
+        _ => return None,
+    };
+
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if integer.is_empty() || !is_digits(&integer) || !is_digits(fraction) {
+        return None;
+    }
+    let number = if fraction.is_empty() {
+        format!("{sign}{integer}")
+    } else {
+        format!("{sign}{integer}.{fraction}")
+    };
+    number.parse().ok()
+}
+
+impl<'de> DeserializeAs<'de, Option<f64>> for LenientNumber {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+        Ok(match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::Number(number) => number.as_f64(),
+            serde_json::Value::String(text) => parse_lenient_number(&text),
+            _ => None,
+        })
+    }
+}
+
+impl<'de> DeserializeAs<'de, Option<u32>> for LenientNumber {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+        let number: Option<f64> = LenientNumber::deserialize_as(deserializer)?;
+        Ok(number
+            .filter(|n| n.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(n))
+            .map(|n| n as u32))
+    }
+}
+
+/// Strips the schema.org prefix of an enumeration member, e.g.
+/// `https://schema.org/InStock` to `InStock`.
+fn enum_member(value: String) -> String {
+    value
+        .trim_start_matches("http://schema.org/")
+        .trim_start_matches("https://schema.org/")
+        .to_owned()
+}
+
+/// https://schema.org/Offer, also used for `AggregateOffer`
+#[serde_as]
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Offer {
+    #[serde_as(as = "LenientNumber")]
+    #[serde(default)]
+    price: Option<f64>,
+    /// Lowest price of an `AggregateOffer`
+    #[serde_as(as = "LenientNumber")]
+    #[serde(default)]
+    low_price: Option<f64>,
+    /// ISO 4217 currency code
+    price_currency: Option<String>,
+    /// `ItemAvailability` member such as `InStock`
+    availability: Option<String>,
+    url: Option<String>,
+}
+
+/// https://schema.org/Product
+#[serde_as]
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Product {
+    #[serde(rename = "@type")]
+    typ: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    #[serde_as(as = "OneOrMany<UrlOrObject>")]
+    #[serde(default)]
+    image: Vec<String>,
+    url: Option<String>,
+    sku: Option<String>,
+    #[serde_as(as = "Option<NameOrObject>")]
+    #[serde(default)]
+    brand: Option<String>,
+    #[serde_as(as = "OneOrMany<_>")]
+    #[serde(default)]
+    offers: Vec<Offer>,
+}
+
+/// Reads a JSON-LD `Product`, returning `None` for other types.
+pub(crate) fn parse_product(value: &serde_json::Value) -> Option<Product> {
+    let mut product = Product::deserialize(value).ok()?;
+    if product.typ.is_none() || !has_type(&product.typ, "Product") {
+        return None;
+    }
+
+    for offer in &mut product.offers {
+        offer.availability = offer.availability.take().map(enum_member);
+    }
+
+    Some(product)
+}
+
+/// https://schema.org/PostalAddress. Addresses given as plain text are kept whole
+/// in `street_address`.
+#[derive(uniffi::Record, Deserialize)]
+#[serde(from = "PostalAddressIn")]
+pub(crate) struct PostalAddress {
+    street_address: Option<String>,
+    locality: Option<String>,
+    region: Option<String>,
+    postal_code: Option<String>,
+    country: Option<String>,
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PostalAddressIn {
+    Text(String),
+    Object {
+        #[serde(rename = "streetAddress")]
+        street_address: Option<String>,
+        #[serde(rename = "addressLocality")]
+        locality: Option<String>,
+        #[serde(rename = "addressRegion")]
+        region: Option<String>,
+        #[serde(rename = "postalCode")]
+        postal_code: Option<String>,
+        /// A `Country` or its ISO 3166-1 code
+        #[serde(rename = "addressCountry")]
+        #[serde_as(as = "Option<NameOrObject>")]
+        #[serde(default)]
+        country: Option<String>,
+    },
+}
+
+impl From<PostalAddressIn> for PostalAddress {
+    fn from(value: PostalAddressIn) -> Self {
+        match value {
+            PostalAddressIn::Text(text) => Self {
+                street_address: Some(text),
+                locality: None,
+                region: None,
+                postal_code: None,
+                country: None,
+            },
+            PostalAddressIn::Object {
+                street_address,
+                locality,
+                region,
+                postal_code,
+                country,
+            } => Self {
+                street_address,
+                locality,
+                region,
+                postal_code,
+                country,
+            },
+        }
+    }
+}
+
+/// https://schema.org/Person, or an `Organization` under whose name something is
+/// booked. A bare name is read as a person with only a `name`.
+#[derive(uniffi::Record, Deserialize)]
+#[serde(from = "PersonIn")]
+pub(crate) struct Person {
+    name: Option<String>,
+    email: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PersonIn {
+    Name(String),
+    Object {
+        name: Option<String>,
+        email: Option<String>,
+    },
+}
+
+impl From<PersonIn> for Person {
+    fn from(value: PersonIn) -> Self {
+        match value {
+            PersonIn::Name(name) => Self {
+                name: Some(name),
+                email: None,
+            },
+            PersonIn::Object { name, email } => Self { name, email },
+        }
+    }
+}
+
+/// https://schema.org/FoodEstablishment, including subtypes like `Restaurant`
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FoodEstablishment {
+    #[serde(rename = "@type")]
+    typ: Option<String>,
+    name: Option<String>,
+    address: Option<PostalAddress>,
+    telephone: Option<String>,
+    url: Option<String>,
+}
+
+/// https://schema.org/FoodEstablishmentReservation
+#[serde_as]
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FoodEstablishmentReservation {
+    #[serde(rename = "@type")]
+    typ: Option<String>,
+    reservation_number: Option<String>,
+    /// `ReservationStatusType` member such as `ReservationConfirmed`
+    reservation_status: Option<String>,
+    #[serde_as(as = "LenientNumber")]
+    #[serde(default)]
+    party_size: Option<u32>,
+    /// `startTime` as given
+    start_time: Option<String>,
+    /// `start_time` as a Unix epoch in milliseconds
+    #[serde(skip)]
+    start: Option<i64>,
+    reservation_for: Option<FoodEstablishment>,
+    under_name: Option<Person>,
+}
+
+/// Reads a JSON-LD `FoodEstablishmentReservation`, returning `None` for other types.
+pub(crate) fn parse_dining_reservation(
+    value: &serde_json::Value,
+) -> Option<FoodEstablishmentReservation> {
+    let mut reservation = FoodEstablishmentReservation::deserialize(value).ok()?;
+    if reservation.typ.is_none() || !has_type(&reservation.typ, "FoodEstablishmentReservation") {
+        return None;
+    }
+
+    reservation.reservation_status = reservation.reservation_status.take().map(enum_member);
+    reservation.start = reservation
+        .start_time
+        .as_deref()
+        .and_then(parse_schema_date);
+
+    Some(reservation)
+}
+
+/// Symbologies a `ticketToken` can be prefixed with, like `qrCode:AB34`
+const TICKET_TOKEN_FORMATS: &[&str] = &["barcode", "barcode128", "qrCode", "aztecCode", "pdf417"];
+
+/// https://schema.org/Ticket
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Ticket {
+    #[serde(rename = "@type")]
+    typ: Option<String>,
+    ticket_number: Option<String>,
+    /// Token as given, e.g. `qrCode:AB34` or a URL to a ticket image
+    ticket_token: Option<String>,
+    /// `ticket_token` without its symbology prefix, the value to encode in the barcode
+    #[serde(skip)]
+    barcode_value: Option<String>,
+    /// Symbology prefix of `ticket_token`, such as `qrCode` or `barcode`
+    #[serde(skip)]
+    barcode_format: Option<String>,
+    under_name: Option<Person>,
+}
+
+/// Reads the tickets of a JSON-LD `Ticket`, or of a reservation holding them in
+/// `reservedTicket` or directly in its own `ticketToken` and `ticketNumber`, like
+/// Gmail's `EventReservation` markup. Tickets without an `underName` take the
+/// reservation's.
+pub(crate) fn parse_tickets(value: &serde_json::Value) -> Vec<Ticket> {
+    let is_reservation = value
+        .get("@type")
+        .and_then(serde_json::Value::as_str)
+        .is_some_and(|typ| typ.ends_with("Reservation"));
+    let values: Vec<&serde_json::Value> = match value.get("reservedTicket") {
+        Some(serde_json::Value::Array(tickets)) if is_reservation => tickets.iter().collect(),
+        Some(ticket) if is_reservation => vec![ticket],
+        _ => vec![value],
+    };
+
+    values
+        .into_iter()
+        .filter_map(|ticket| Ticket::deserialize(ticket).ok())
+        .filter(|ticket| {
+            let is_ticket =
+                is_reservation || (ticket.typ.is_some() && has_type(&ticket.typ, "Ticket"));
+            is_ticket && (ticket.ticket_number.is_some() || ticket.ticket_token.is_some())
+        })
+        .map(|mut ticket| {
+            if ticket.under_name.is_none() && is_reservation {
+                ticket.under_name = value
+                    .get("underName")
+                    .and_then(|name| Person::deserialize(name).ok());
+            }
+            let token = ticket.ticket_token.as_deref().map(str::trim);
+            if let Some((format, barcode)) = token.and_then(|token| token.split_once(':')) {
+                if TICKET_TOKEN_FORMATS
+                    .iter()
+                    .any(|f| f.eq_ignore_ascii_case(format))
+                {
+                    ticket.barcode_format = Some(format.to_owned());
+                    ticket.barcode_value = Some(barcode.trim().to_owned());
+                }
+            }
+            ticket
+        })
+        .collect()
+}
+
+/// https://schema.org/Airport
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Airport {
+    name: Option<String>,
+    iata_code: Option<String>,
+}
+
+/// https://schema.org/Airline
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Airline {
+    name: Option<String>,
+    iata_code: Option<String>,
+}
+
+/// https://schema.org/Flight
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Flight {
+    flight_number: Option<String>,
+    airline: Option<Airline>,
+    departure_airport: Option<Airport>,
+    arrival_airport: Option<Airport>,
+    departure_gate: Option<String>,
+    departure_terminal: Option<String>,
+    /// `departureTime` as given
+    departure_time: Option<String>,
+    /// `departure_time` as a Unix epoch in milliseconds
+    #[serde(skip)]
+    departure: Option<i64>,
+    /// `arrivalTime` as given
+    arrival_time: Option<String>,
+    /// `arrival_time` as a Unix epoch in milliseconds
+    #[serde(skip)]
+    arrival: Option<i64>,
+}
+
+/// https://schema.org/FlightReservation, with the boarding pass details Gmail's
+/// markup adds
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FlightReservation {
+    #[serde(rename = "@type")]
+    typ: Option<String>,
+    reservation_number: Option<String>,
+    /// `ReservationStatusType` member such as `ReservationConfirmed`
+    reservation_status: Option<String>,
+    reservation_for: Option<Flight>,
+    under_name: Option<Person>,
+    /// `airplaneSeat`, or else the `seatNumber` of the reserved ticket's `ticketedSeat`
+    airplane_seat: Option<String>,
+    boarding_group: Option<String>,
+    /// The boarding pass, with its barcode split into format and value
+    #[serde(skip)]
+    ticket: Option<Ticket>,
+}
+
+/// Reads a JSON-LD `FlightReservation`, returning `None` for other types.
+pub(crate) fn parse_flight_reservation(value: &serde_json::Value) -> Option<FlightReservation> {
+    let mut reservation = FlightReservation::deserialize(value).ok()?;
+    if reservation.typ.is_none() || !has_type(&reservation.typ, "FlightReservation") {
+        return None;
+    }
+
+    reservation.reservation_status = reservation.reservation_status.take().map(enum_member);
+    reservation.ticket = parse_tickets(value).into_iter().next();
+    if reservation.airplane_seat.is_none() {
+        let ticket = match value.get("reservedTicket") {
+            Some(serde_json::Value::Array(tickets)) => tickets.first(),
+            ticket => ticket,
+        };
+        reservation.airplane_seat = ticket
+            .and_then(|ticket| ticket.pointer("/ticketedSeat/seatNumber"))
+            .and_then(serde_json::Value::as_str)
+            .map(ToOwned::to_owned);
+    }
+    if let Some(flight) = &mut reservation.reservation_for {
+        flight.departure = flight.departure_time.as_deref().and_then(parse_schema_date);
+        flight.arrival = flight.arrival_time.as_deref().and_then(parse_schema_date);
+    }
+
+    Some(reservation)
+}
+
+/// https://schema.org/PriceSpecification, also read from a `MonetaryAmount` or a
+/// bare number
+#[derive(uniffi::Record, Deserialize)]
+#[serde(from = "PriceSpecificationIn")]
+pub(crate) struct PriceSpecification {
+    price: Option<f64>,
+    /// ISO 4217 currency code
+    price_currency: Option<String>,
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PriceSpecificationIn {
+    Object {
+        #[serde(alias = "value")]
+        #[serde_as(as = "LenientNumber")]
+        #[serde(default)]
+        price: Option<f64>,
+        #[serde(rename = "priceCurrency", alias = "currency")]
+        price_currency: Option<String>,
+    },
+    Price(#[serde_as(as = "LenientNumber")] Option<f64>),
+}
+
+impl From<PriceSpecificationIn> for PriceSpecification {
+    fn from(value: PriceSpecificationIn) -> Self {
+        match value {
+            PriceSpecificationIn::Object {
+                price,
+                price_currency,
+            } => Self {
+                price,
+                price_currency,
+            },
+            PriceSpecificationIn::Price(price) => Self {
+                price,
+                price_currency: None,
+            },
+        }
+    }
+}
+
+/// https://schema.org/Invoice
+#[serde_as]
+#[derive(uniffi::Record, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Invoice {
+    #[serde(rename = "@type")]
+    typ: Option<String>,
+    confirmation_number: Option<String>,
+    total_payment_due: Option<PriceSpecification>,
+    /// `PaymentStatusType` member such as `PaymentDue` or `PaymentComplete`
+    payment_status: Option<String>,
+    /// `paymentDueDate` as given
+    payment_due_date: Option<String>,
+    /// `payment_due_date` as a Unix epoch in milliseconds
+    #[serde(skip)]
+    payment_due: Option<i64>,
+    #[serde_as(as = "Option<NameOrObject>")]
+    #[serde(default)]
+    provider: Option<String>,
+    url: Option<String>,
+}
+
+/// The parts of a https://schema.org/Order read as an `Invoice`
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OrderIn {
+    order_number: Option<String>,
+    #[serde_as(as = "LenientNumber")]
+    #[serde(default)]
+    price: Option<f64>,
+    price_currency: Option<String>,
+    payment_due_date: Option<String>,
+    #[serde_as(as = "Option<NameOrObject>")]
+    #[serde(default)]
+    seller: Option<String>,
+    url: Option<String>,
+    part_of_invoice: Option<Invoice>,
+}
+
+/// Reads a JSON-LD `Invoice`, or an `Order` through its `partOfInvoice` or else its
+/// own price, returning `None` for other types.
+pub(crate) fn parse_invoice(value: &serde_json::Value) -> Option<Invoice> {
+    let typ = value
+        .get("@type")
+        .and_then(serde_json::Value::as_str)
+        .map(ToOwned::to_owned);
+    let mut invoice = if typ.is_some() && has_type(&typ, "Invoice") {
+        Invoice::deserialize(value).ok()?
+    } else if typ.is_some() && has_type(&typ, "Order") {
+        let order = OrderIn::deserialize(value).ok()?;
+        order.part_of_invoice.unwrap_or(Invoice {
+            typ,
+            confirmation_number: order.order_number,
+            total_payment_due: order.price.map(|price| PriceSpecification {
+                price: Some(price),
+                price_currency: order.price_currency,
+            }),
+            payment_status: None,
+            payment_due_date: order.payment_due_date,
+            payment_due: None,
+            provider: order.seller,
+            url: order.url,
+        })
+    } else {
+        return None;
+    };
+
+    invoice.payment_status = invoice.payment_status.take().map(enum_member);
+    invoice.payment_due = invoice
+        .payment_due_date
+        .as_deref()
+        .and_then(parse_schema_date);
+
+    Some(invoice)
+}
+
+/// Organization types read as a sender's identity, besides `Person`
+const ORGANIZATION_TYPES: &[&str] = &[
+    "Organization",
+    "Corporation",
+    "LocalBusiness",
+    "NewsMediaOrganization",
+    "OnlineBusiness",
+];
+
+/// https://schema.org/Organization, also read from a `Person`, whose `image` is
+/// taken as the logo
+#[derive(uniffi::Record, Deserialize)]
+#[serde(from = "OrganizationIn")]
+pub(crate) struct Organization {
+    /// `Person` when read from one
+    typ: Option<String>,
+    name: Option<String>,
+    logo: Option<String>,
+    url: Option<String>,
+    /// Profiles of the same entity elsewhere, usually social media
+    same_as: Vec<String>,
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OrganizationIn {
+    #[serde(rename = "@type")]
+    typ: Option<String>,
+    name: Option<String>,
+    #[serde_as(as = "OneOrMany<UrlOrObject>")]
+    #[serde(default)]
+    logo: Vec<String>,
+    #[serde_as(as = "OneOrMany<UrlOrObject>")]
+    #[serde(default)]
+    image: Vec<String>,
+    url: Option<String>,
+    #[serde_as(as = "OneOrMany<_>")]
+    #[serde(default)]
+    same_as: Vec<String>,
+}
+
+impl From<OrganizationIn> for Organization {
+    fn from(value: OrganizationIn) -> Self {
+        Self {
+            typ: value.typ.map(enum_member),
+            name: value.name,
+            logo: value.logo.into_iter().chain(value.image).next(),
+            url: value.url,
+            same_as: value.same_as,
+        }
+    }
+}
+
+/// Reads a JSON-LD `Organization` or `Person`, returning `None` for other types.
+pub(crate) fn parse_sender_identity(value: &serde_json::Value) -> Option<Organization> {
+    let identity = Organization::deserialize(value).ok()?;
+    let is_identity = ORGANIZATION_TYPES
+        .iter()
+        .chain(&["Person"])
+        .any(|expected| identity.typ.is_some() && has_type(&identity.typ, expected));
+    if !is_identity || (identity.name.is_none() && identity.logo.is_none()) {
+        return None;
+    }
+
+    Some(identity)
+}
+
+/// Whether the identity was read from an organization rather than a `Person`
+pub(crate) fn is_organization(identity: &Organization) -> bool {
+    identity.typ.as_deref() != Some("Person")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_pronounceable_text() {
+        let text = parse_pronounceable_text(
+            r#"{
+                "@context": "https://schema.org",
+                "@type": "PronounceableText",
+                "textValue": "Worcester",
+                "phoneticText": "/ˈwʊstə/",
+                "speechToTextMarkup": "IPA",
+                "inLanguage": [
+                    "en-GB",
+                    { "@type": "Language", "name": "English", "alternateName": "en" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(text.phonetic_text.as_deref(), Some("/ˈwʊstə/"));
+        assert_eq!(
+            text.in_language,
+            vec![
+                Language {
+                    name: None,
+                    code: Some("en-GB".to_owned()),
+                },
+                Language {
+                    name: Some("English".to_owned()),
+                    code: Some("en".to_owned()),
+                },
+            ]
+        );
+
+        let text = parse_pronounceable_text(r#"{"textValue": "Reading", "inLanguage": "en"}"#);
+        assert_eq!(text.unwrap().in_language.len(), 1);
+        assert!(parse_pronounceable_text(r#"{"@type": "Person"}"#).is_none());
+    }
+
+    #[test]
+    fn parses_products() {
+        let value = serde_json::json!({
+            "@context": "https://schema.org",
+            "@type": "Product",
+            "name": "Trail shoes",
+            "image": { "@type": "ImageObject", "url": "https://example.com/shoe.jpg" },
+            "brand": { "@type": "Brand", "name": "Acme" },
+            "offers": [
+                {
+                    "@type": "Offer",
+                    "price": "89.99",
+                    "priceCurrency": "USD",
+                    "availability": "https://schema.org/InStock"
+                },
+                { "@type": "AggregateOffer", "lowPrice": 75, "priceCurrency": "USD" }
+            ]
+        });
+        let product = parse_product(&value).unwrap();
+
+        assert_eq!(product.image, ["https://example.com/shoe.jpg"]);
+        assert_eq!(product.brand.as_deref(), Some("Acme"));
+        assert_eq!(product.offers[0].price, Some(89.99));
+        assert_eq!(product.offers[0].availability.as_deref(), Some("InStock"));
+        assert_eq!(product.offers[1].low_price, Some(75.0));
+
+        let value = serde_json::json!({ "@type": "Product", "offers": { "price": "call us" } });
+        assert_eq!(parse_product(&value).unwrap().offers[0].price, None);
+        assert!(parse_product(&serde_json::json!({ "@type": "Order" })).is_none());
+    }
+
+    #[test]
+    fn parses_dining_reservations() {
+        let value = serde_json::json!({
+            "@context": "https://schema.org",
+            "@type": "FoodEstablishmentReservation",
+            "reservationNumber": "OT12345",
+            "reservationStatus": "https://schema.org/ReservationConfirmed",
+            "partySize": "4",
+            "startTime": "2027-04-10T19:30:00-07:00",
+            "underName": { "@type": "Person", "name": "John Smith" },
+            "reservationFor": {
+                "@type": "Restaurant",
+                "name": "Wagamama",
+                "address": {
+                    "@type": "PostalAddress",
+                    "streetAddress": "1 Tavistock St",
+                    "addressLocality": "London",
+                    "addressCountry": { "@type": "Country", "name": "GB" }
+                }
+            }
+        });
+        let reservation = parse_dining_reservation(&value).unwrap();
+
+        assert_eq!(reservation.reservation_number.as_deref(), Some("OT12345"));
+        assert_eq!(
+            reservation.reservation_status.as_deref(),
+            Some("ReservationConfirmed")
+        );
+        assert_eq!(reservation.party_size, Some(4));
+        assert_eq!(reservation.start, Some(1_807_410_600_000));
+        assert_eq!(
+            reservation.under_name.unwrap().name.as_deref(),
+            Some("John Smith")
+        );
+        let restaurant = reservation.reservation_for.unwrap();
+        assert_eq!(restaurant.name.as_deref(), Some("Wagamama"));
+        let address = restaurant.address.unwrap();
+        assert_eq!(address.locality.as_deref(), Some("London"));
+        assert_eq!(address.country.as_deref(), Some("GB"));
+
+        let value = serde_json::json!({
+            "@type": "FoodEstablishmentReservation",
+            "partySize": 2.5,
+            "underName": "Jane",
+            "reservationFor": { "name": "Dishoom", "address": "7 Boundary St, London" }
+        });
+        let reservation = parse_dining_reservation(&value).unwrap();
+        assert_eq!(reservation.party_size, None);
+        assert_eq!(
+            reservation.under_name.unwrap().name.as_deref(),
+            Some("Jane")
+        );
+        let address = reservation.reservation_for.unwrap().address.unwrap();
+        assert_eq!(
+            address.street_address.as_deref(),
+            Some("7 Boundary St, London")
+        );
+        assert!(parse_dining_reservation(&serde_json::json!({ "@type": "Product" })).is_none());
+    }
+
+    #[test]
+    fn parses_tickets() {
+        let value = serde_json::json!({
+            "@type": "EventReservation",
+            "underName": { "@type": "Person", "name": "John Smith" },
+            "reservedTicket": [
+                { "@type": "Ticket", "ticketNumber": "A1", "ticketToken": "barcode:0123456789" },
+                {
+                    "@type": "Ticket",
+                    "ticketNumber": "A2",
+                    "ticketToken": "qrCode:XYZ",
+                    "underName": "Jane Smith"
+                }
+            ]
+        });
+        let tickets = parse_tickets(&value);
+
+        assert_eq!(tickets.len(), 2);
+        assert_eq!(tickets[0].barcode_value.as_deref(), Some("0123456789"));
+        assert_eq!(tickets[0].barcode_format.as_deref(), Some("barcode"));
+        assert_eq!(
+            tickets[0].under_name.as_ref().unwrap().name.as_deref(),
+            Some("John Smith")
+        );
+        assert_eq!(tickets[1].barcode_format.as_deref(), Some("qrCode"));
+        assert_eq!(
+            tickets[1].under_name.as_ref().unwrap().name.as_deref(),
+            Some("Jane Smith")
+        );
+
+        let value = serde_json::json!({
+            "@type": "EventReservation",
+            "ticketNumber": "B7",
+            "ticketToken": "https://example.com/ticket.png"
+        });
+        let tickets = parse_tickets(&value);
+        assert_eq!(tickets[0].ticket_number.as_deref(), Some("B7"));
+        assert_eq!(tickets[0].barcode_value, None);
+
+        assert!(parse_tickets(&serde_json::json!({ "@type": "Ticket" })).is_empty());
+        assert!(
+            parse_tickets(&serde_json::json!({ "@type": "Order", "ticketNumber": "1" })).is_empty()
+        );
+    }
+
+    #[test]
+    fn parses_invoices() {
+        let value = serde_json::json!({
+            "@context": "https://schema.org",
+            "@type": "Invoice",
+            "confirmationNumber": "INV-42",
+            "paymentStatus": "https://schema.org/PaymentDue",
+            "paymentDueDate": "2027-05-01",
+            "provider": { "@type": "Organization", "name": "Acme Power" },
+            "totalPaymentDue": {
+                "@type": "PriceSpecification",
+                "price": "70.50",
+                "priceCurrency": "EUR"
+            }
+        });
+        let invoice = parse_invoice(&value).unwrap();
+
+        let total = invoice.total_payment_due.unwrap();
+        assert_eq!(total.price, Some(70.5));
+        assert_eq!(total.price_currency.as_deref(), Some("EUR"));
+        assert_eq!(invoice.payment_status.as_deref(), Some("PaymentDue"));
+        assert_eq!(invoice.payment_due, Some(1_809_129_600_000));
+        assert_eq!(invoice.provider.as_deref(), Some("Acme Power"));
+
+        let value = serde_json::json!({
+            "@type": "Invoice",
+            "totalPaymentDue": { "@type": "MonetaryAmount", "value": 12, "currency": "USD" }
+        });
+        let total = parse_invoice(&value).unwrap().total_payment_due.unwrap();
+        assert_eq!(total.price, Some(12.0));
+        assert_eq!(total.price_currency.as_deref(), Some("USD"));
+
+        let value = serde_json::json!({
+            "@type": "Order",
+            "orderNumber": "1234",
+            "price": 29.99,
+            "priceCurrency": "USD",
+            "seller": { "@type": "Organization", "name": "Shop" }
+        });
+        let invoice = parse_invoice(&value).unwrap();
+        assert_eq!(invoice.confirmation_number.as_deref(), Some("1234"));
+        assert_eq!(invoice.total_payment_due.unwrap().price, Some(29.99));
+        assert!(parse_invoice(&serde_json::json!({ "@type": "Product" })).is_none());
+    }
+
+    #[test]
+    fn parses_sender_identities() {
+        let identity = parse_sender_identity(&serde_json::json!({
+            "@context": "https://schema.org",
+            "@type": "Organization",
+            "name": "Acme",
+            "url": "https://acme.example",
+            "logo": { "@type": "ImageObject", "url": "https://acme.example/logo.png" },
+            "sameAs": ["https://x.com/acme", "https://www.linkedin.com/company/acme"]
+        }))
+        .unwrap();
+
+        assert!(is_organization(&identity));
+        assert_eq!(identity.name.as_deref(), Some("Acme"));
+        assert_eq!(
+            identity.logo.as_deref(),
+            Some("https://acme.example/logo.png")
+        );
+        assert_eq!(identity.same_as.len(), 2);
+
+        let person = parse_sender_identity(&serde_json::json!({
+            "@type": "Person",
+            "name": "Ada",
+            "image": "https://ada.example/me.jpg",
+            "sameAs": "https://github.com/ada"
+        }))
+        .unwrap();
+
+        assert!(!is_organization(&person));
+        assert_eq!(person.logo.as_deref(), Some("https://ada.example/me.jpg"));
+        assert_eq!(person.same_as, vec!["https://github.com/ada".to_owned()]);
+
+        assert!(
+            parse_sender_identity(&serde_json::json!({"@type": "Product", "name": "Shoe"}))
+                .is_none()
+        );
+        assert!(parse_sender_identity(&serde_json::json!({"@type": "Organization"})).is_none());
+    }
+
+    #[test]
+    fn parses_flight_reservations() {
+        let value = serde_json::json!({
+            "@context": "http://schema.org",
+            "@type": "FlightReservation",
+            "reservationNumber": "RXJ34P",
+            "reservationStatus": "http://schema.org/ReservationConfirmed",
+            "underName": { "@type": "Person", "name": "Eva Green" },
+            "reservationFor": {
+                "@type": "Flight",
+                "flightNumber": "110",
+                "airline": { "@type": "Airline", "name": "United", "iataCode": "UA" },
+                "departureAirport": { "@type": "Airport", "name": "San Francisco Airport", "iataCode": "SFO" },
+                "departureTime": "2027-03-04T20:15:00-08:00",
+                "departureGate": "B12",
+                "arrivalAirport": { "@type": "Airport", "name": "John F. Kennedy International Airport", "iataCode": "JFK" },
+                "arrivalTime": "2027-03-05T06:30:00-05:00"
+            },
+            "airplaneSeat": "9A",
+            "boardingGroup": "B",
+            "ticketNumber": "123XYZ",
+            "ticketToken": "aztecCode:M1GREEN/EVA RXJ34P SFOJFKUA 0110"
+        });
+        let reservation = parse_flight_reservation(&value).unwrap();
+
+        assert_eq!(
+            reservation.reservation_status.as_deref(),
+            Some("ReservationConfirmed")
+        );
+        assert_eq!(reservation.airplane_seat.as_deref(), Some("9A"));
+        assert_eq!(reservation.boarding_group.as_deref(), Some("B"));
+        let flight = reservation.reservation_for.unwrap();
+        assert_eq!(flight.departure_gate.as_deref(), Some("B12"));
+        assert_eq!(flight.airline.unwrap().iata_code.as_deref(), Some("UA"));
+        assert_eq!(flight.departure, Some(1_804_220_100_000));
+        let ticket = reservation.ticket.unwrap();
+        assert_eq!(ticket.ticket_number.as_deref(), Some("123XYZ"));
+        assert_eq!(ticket.barcode_format.as_deref(), Some("aztecCode"));
+        assert_eq!(
+            ticket.barcode_value.as_deref(),
+            Some("M1GREEN/EVA RXJ34P SFOJFKUA 0110")
+        );
+
+        let value = serde_json::json!({
+            "@type": "FlightReservation",
+            "reservedTicket": {
+                "@type": "Ticket",
+                "ticketToken": "qrCode:ABC",
+                "ticketedSeat": { "@type": "Seat", "seatNumber": "22C" }
+            }
+        });
+        let reservation = parse_flight_reservation(&value).unwrap();
+        assert_eq!(reservation.airplane_seat.as_deref(), Some("22C"));
+        assert_eq!(
+            reservation.ticket.unwrap().barcode_value.as_deref(),
+            Some("ABC")
+        );
+
+        assert!(
+            parse_flight_reservation(&serde_json::json!({"@type": "LodgingReservation"})).is_none()
+        );
+    }
+
+    #[test]
+    fn parses_lenient_numbers() {
+        assert_eq!(parse_lenient_number("19,99"), Some(19.99));
+        assert_eq!(parse_lenient_number("1.299,00"), Some(1299.0));
+        assert_eq!(parse_lenient_number("1,299.00"), Some(1299.0));
+        assert_eq!(parse_lenient_number(" 89.99 "), Some(89.99));
+        assert_eq!(parse_lenient_number("-4"), Some(-4.0));
+        assert_eq!(parse_lenient_number("1,299"), None);
+        assert_eq!(parse_lenient_number("1.2.3"), None);
+        assert_eq!(parse_lenient_number("12,34.5"), None);
+        assert_eq!(parse_lenient_number("call us"), None);
+
+        let value = serde_json::json!({ "@type": "Product", "offers": { "price": "19,99" } });
+        assert_eq!(parse_product(&value).unwrap().offers[0].price, Some(19.99));
+    }
+}
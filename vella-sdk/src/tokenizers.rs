@@ -1,7 +1,21 @@
-use std::{fmt::Display, str::FromStr};
+use std::{borrow::Cow, fmt::Display, str::FromStr};
 
 use rayon::prelude::*;
-use tokenizers::Tokenizer;
+use tokenizers::{
+    normalizers::{
+        unicode::{NFC, NFD, NFKC, NFKD},
+        NormalizerWrapper, Sequence,
+    },
+    processors::{
+        bert::BertProcessing, roberta::RobertaProcessing, template::TemplateProcessing,
+        PostProcessorWrapper,
+    },
+    utils::{padding::pad_encodings, truncation::truncate_encodings},
+    EncodeInput, Encoding, Model, NormalizedString, Normalizer, OffsetType, PostProcessor,
+    PreTokenizer, Tokenizer,
+};
+
+use crate::email::NormalizationForm;
 
 #[derive(uniffi::Record)]
 struct Token {
@@ -9,6 +23,69 @@ struct Token {
     token: String,
     start: u32,
     end: u32,
+    /// Index of the input word this token belongs to, `None` for special tokens
+    word_id: Option<u32>,
+}
+
+/// Consecutive tokens belonging to the same input word.
+#[derive(uniffi::Record)]
+struct WordGroup {
+    word_id: u32,
+    /// Token strings joined together, without WordPiece `##` continuation markers
+    text: String,
+    start: u32,
+    end: u32,
+    token_ids: Vec<u32>,
+}
+
+fn tokens_from_encoding(encoding: &Encoding) -> Vec<Token> {
+    encoding
+        .get_tokens()
+        .iter()
+        .cloned()
+        .zip(encoding.get_ids().iter().cloned())
+        .zip(encoding.get_offsets().iter().cloned())
+        .zip(encoding.get_word_ids().iter().cloned())
+        .map(|(((token, id), (start, end)), word_id)| Token {
+            id,
+            token,
+            start: start as u32,
+            end: end as u32,
+            word_id,
+        })
+        .collect()
+}
+
+/// Merges sub-word tokens sharing a `word_id` into words. Special tokens, which
+/// have no `word_id`, are dropped.
+#[uniffi::export]
+fn group_by_word(tokens: Vec<Token>) -> Vec<WordGroup> {
+    let mut groups: Vec<WordGroup> = Vec::new();
+
+    for token in tokens {
+        let Some(word_id) = token.word_id else {
+            continue;
+        };
+
+        match groups.last_mut() {
+            Some(group) if group.word_id == word_id => {
+                group
+                    .text
+                    .push_str(token.token.strip_prefix("##").unwrap_or(&token.token));
+                group.end = token.end;
+                group.token_ids.push(token.id);
+            }
+            _ => groups.push(WordGroup {
+                word_id,
+                text: token.token,
+                start: token.start,
+                end: token.end,
+                token_ids: vec![token.id],
+            }),
+        }
+    }
+
+    groups
 }
 
 #[derive(uniffi::Record)]
@@ -22,6 +99,136 @@ struct TokenizedBatch {
     token_ids: Vec<Vec<u32>>,
     attention_mask: Vec<Vec<u32>>,
     type_ids: Vec<Vec<u32>>,
+    /// Index of the input word each token belongs to, `None` for special and padding tokens
+    word_ids: Vec<Vec<Option<u32>>>,
+    /// 1 for special tokens like `[CLS]`, added tokens marked special and padding,
+    /// 0 for tokens of the input, e.g. to leave them out of mean pooling
+    special_tokens_mask: Vec<Vec<u32>>,
+}
+
+/// A batch padded to its longest sequence, as row-major `batch_size × seq_len`
+/// matrices ready to reshape into model inputs.
+#[derive(uniffi::Record)]
+struct PaddedMatrix {
+    input_ids: Vec<u32>,
+    attention_mask: Vec<u32>,
+    type_ids: Vec<u32>,
+    seq_len: u32,
+    batch_size: u32,
+}
+
+/// How many tokens of an input the configured truncation keeps.
+#[derive(uniffi::Record)]
+struct TruncationInfo {
+    /// Tokens in the input without truncation, including special tokens
+    total_tokens: u32,
+    /// Tokens left after truncation, not counting padding
+    kept_tokens: u32,
+    truncated_tokens: u32,
+}
+
+/// Token counts over a batch of inputs, from `token_length_stats`.
+#[derive(uniffi::Record, Debug, PartialEq)]
+struct LengthStats {
+    min: u32,
+    max: u32,
+    mean: f64,
+    /// Median, as the nearest-rank percentile
+    p50: u32,
+    p95: u32,
+    /// Tokens across all inputs
+    total: u64,
+}
+
+/// Result of `roundtrip_check`.
+#[derive(uniffi::Record)]
+struct RoundtripResult {
+    /// Whether `decoded` matches the input, either as given or after the tokenizer's
+    /// normalizer is applied to it
+    lossless: bool,
+    decoded: String,
+}
+
+/// The single-input counterpart of `TokenizedBatch`, with every field of an encoding
+/// so callers needing several of them encode once.
+#[derive(uniffi::Record)]
+struct TokenizedInput {
+    token_ids: Vec<u32>,
+    tokens: Vec<String>,
+    /// Byte offsets of each token into the input
+    offsets: Vec<TokenOffset>,
+    /// 1 for real tokens and 0 for padding
+    attention_mask: Vec<u32>,
+    type_ids: Vec<u32>,
+    /// 1 for special and padding tokens (see `TokenizedBatch::special_tokens_mask`)
+    special_tokens_mask: Vec<u32>,
+}
+
+#[derive(uniffi::Record, Debug, PartialEq)]
+struct TokenOffset {
+    start: u32,
+    end: u32,
+}
+
+impl From<&Encoding> for TokenizedInput {
+    fn from(encoding: &Encoding) -> Self {
+        Self {
+            token_ids: encoding.get_ids().to_vec(),
+            tokens: encoding.get_tokens().to_vec(),
+            offsets: encoding
+                .get_offsets()
+                .iter()
+                .map(|&(start, end)| TokenOffset {
+                    start: start as u32,
+                    end: end as u32,
+                })
+                .collect(),
+            attention_mask: encoding.get_attention_mask().to_vec(),
+            type_ids: encoding.get_type_ids().to_vec(),
+            special_tokens_mask: encoding.get_special_tokens_mask().to_vec(),
+        }
+    }
+}
+
+impl TokenizedBatch {
+    fn from_encodings(encodings: &[Encoding]) -> Self {
+        let token_ids: Vec<_> = encodings.par_iter().map(|e| e.get_ids().to_vec()).collect();
+
+        let attention_mask: Vec<_> = encodings
+            .par_iter()
+            .map(|e| e.get_attention_mask().to_vec())
+            .collect();
+
+        let type_ids: Vec<_> = encodings
+            .par_iter()
+            .map(|e| e.get_type_ids().to_vec())
+            .collect();
+
+        let word_ids: Vec<_> = encodings
+            .par_iter()
+            .map(|e| e.get_word_ids().to_vec())
+            .collect();
+
+        let special_tokens_mask: Vec<_> = encodings
+            .par_iter()
+            .map(|e| e.get_special_tokens_mask().to_vec())
+            .collect();
+
+        Self {
+            token_ids,
+            attention_mask,
+            type_ids,
+            word_ids,
+            special_tokens_mask,
+        }
+    }
+}
+
+/// Two sequences encoded together, e.g. a (query, passage) pair for a cross-encoder.
+#[derive(uniffi::Record)]
+struct SequencePair {
+    first: String,
+    second: String,
 }
 
 #[derive(uniffi::Enum, Debug)]
@@ -143,11 +350,68 @@ impl From<TruncationStrategy> for tokenizers::TruncationStrategy {
     }
 }
 
+/// Model families with well-known special-token conventions.
+#[derive(uniffi::Enum, Debug)]
+pub enum ModelFamily {
+    /// `[CLS] A [SEP]` and `[CLS] A [SEP] B [SEP]`
+    Bert,
+    /// `<s> A </s>` and `<s> A </s></s> B </s>`
+    Roberta,
+    /// `A </s>` and `A </s> B </s>`, as used by T5-style sentencepiece models
+    SentencePiece,
+}
+
+impl ModelFamily {
+    /// Builds the family's post-processor, or `None` when the vocabulary lacks its
+    /// special tokens.
+    fn default_post_processor(&self, tokenizer: &Tokenizer) -> Option<PostProcessorWrapper> {
+        let special_token = |token: &str| {
+            tokenizer
+                .token_to_id(token)
+                .map(|id| (token.to_owned(), id))
+        };
+
+        match self {
+            ModelFamily::Bert => {
+                Some(BertProcessing::new(special_token("[SEP]")?, special_token("[CLS]")?).into())
+            }
+            ModelFamily::Roberta => {
+                Some(RobertaProcessing::new(special_token("</s>")?, special_token("<s>")?).into())
+            }
+            ModelFamily::SentencePiece => {
+                let eos = special_token("</s>")?;
+                TemplateProcessing::builder()
+                    .try_single("$A </s>")
+                    .ok()?
+                    .try_pair("$A </s> $B:1 </s>:1")
+                    .ok()?
+                    .special_tokens(vec![eos])
+                    .build()
+                    .ok()
+                    .map(Into::into)
+            }
+        }
+    }
+}
+
+impl From<NormalizationForm> for NormalizerWrapper {
+    fn from(value: NormalizationForm) -> Self {
+        match value {
+            NormalizationForm::Nfc => NFC.into(),
+            NormalizationForm::Nfd => NFD.into(),
+            NormalizationForm::Nfkc => NFKC.into(),
+            NormalizationForm::Nfkd => NFKD.into(),
+        }
+    }
+}
+
 #[derive(uniffi::Error, Debug)]
 pub enum TokenizeError {
     TokenizerCreationFailed,
     InputEncodingFailed,
     InvalidTruncationParams,
+    OutputDecodingFailed,
+    InvalidChunkParams,
 }
 
 impl Display for TokenizeError {
@@ -156,6 +420,8 @@ impl Display for TokenizeError {
             TokenizeError::TokenizerCreationFailed => write!(f, "Tokenizer creation failed"),
             TokenizeError::InputEncodingFailed => write!(f, "Input encoding failed"),
             TokenizeError::InvalidTruncationParams => write!(f, "Invalid truncation params"),
+            TokenizeError::OutputDecodingFailed => write!(f, "Output decoding failed"),
+            TokenizeError::InvalidChunkParams => write!(f, "Invalid chunk params"),
         }
     }
 }
@@ -169,15 +435,40 @@ struct CustomTokenizerInner {
 #[uniffi::export]
 impl CustomTokenizerInner {
     /// Creates a new custom tokenizer.
-    #[uniffi::constructor]
+    ///
+    /// When `model_family` is given and the dictionary doesn't configure a post-processor,
+    /// that family's special-token template is used instead.
+    ///
+    /// `input_normalization` normalizes inputs before the dictionary's own normalizer,
+    /// if any. Offsets still point into the original input.
+    #[uniffi::constructor(default(model_family = None, input_normalization = None))]
     fn new(
         dictionary: &str,
         padding: Option<PaddingParams>,
         truncation: Option<TruncationParams>,
+        model_family: Option<ModelFamily>,
+        input_normalization: Option<NormalizationForm>,
     ) -> Result<Self, TokenizeError> {
         let mut tokenizer =
             Tokenizer::from_str(dictionary).map_err(|_| TokenizeError::TokenizerCreationFailed)?;
 
+        if let Some(model_family) = model_family {
+            if tokenizer.get_post_processor().is_none() {
+                if let Some(processor) = model_family.default_post_processor(&tokenizer) {
+                    tokenizer.with_post_processor(Some(processor));
+                }
+            }
+        }
+
+        if let Some(form) = input_normalization {
+            let form = NormalizerWrapper::from(form);
+            let normalizer = match tokenizer.get_normalizer() {
+                Some(normalizer) => Sequence::new(vec![form, normalizer.clone()]).into(),
+                None => form,
+            };
+            tokenizer.with_normalizer(Some(normalizer));
+        }
+
         if let Some(padding) = padding {
             tokenizer.with_padding(Some(padding.into()));
         }
@@ -191,34 +482,142 @@ impl CustomTokenizerInner {
     }
 }
 
+impl CustomTokenizerInner {
+    /// Encodes a single input, which every single-input method shares.
+    fn encode_single(
+        &self,
+        input: &str,
+        special_tokens: SpecialTokens,
+    ) -> Result<Encoding, TokenizeError> {
+        self.tokenizer
+            .encode(input, special_tokens.into())
+            .map_err(|_| TokenizeError::InputEncodingFailed)
+    }
+
+    /// A copy of the tokenizer without truncation or padding, to count every token of
+    /// an input. Cloning copies the whole vocabulary, so callers skip this when the
+    /// tokenizer has neither.
+    fn untruncated(&self) -> Result<Tokenizer, TokenizeError> {
+        let mut untruncated = self.tokenizer.clone();
+        untruncated
+            .with_truncation(None)
+            .map_err(|_| TokenizeError::InvalidTruncationParams)?
+            .with_padding(None);
+        Ok(untruncated)
+    }
+
+    /// The tokenizer itself when it has no truncation or padding, `untruncated` otherwise.
+    fn counting_tokenizer(&self) -> Result<Cow<'_, Tokenizer>, TokenizeError> {
+        if self.tokenizer.get_truncation().is_some() || self.tokenizer.get_padding().is_some() {
+            self.untruncated().map(Cow::Owned)
+        } else {
+            Ok(Cow::Borrowed(&self.tokenizer))
+        }
+    }
+
+    /// Encodes an input with special tokens like `Tokenizer::encode`, but truncated with
+    /// `truncation` instead of the configured `TruncationParams`, and never padded.
+    /// This runs the tokenizer's own steps one by one, so per-call settings don't need
+    /// a copy of the tokenizer.
+    fn encode_truncated_with(
+        &self,
+        input: &str,
+        truncation: Option<&tokenizers::TruncationParams>,
+    ) -> tokenizers::Result<Encoding> {
+        let tokenizer = &self.tokenizer;
+        let mut pretokenized = tokenizer
+            .get_added_vocabulary()
+            .extract_and_normalize(tokenizer.get_normalizer(), input);
+        if let Some(pre_tokenizer) = tokenizer.get_pre_tokenizer() {
+            pre_tokenizer.pre_tokenize(&mut pretokenized)?;
+        }
+        pretokenized.tokenize(|normalized| tokenizer.get_model().tokenize(normalized.get()))?;
+        let encoding = pretokenized.into_encoding(None, 0, OffsetType::Byte)?;
+
+        let processor = tokenizer.get_post_processor();
+        let encoding = match truncation {
+            Some(truncation) => {
+                // Leaves room for the special tokens, like `post_process` does
+                let added_tokens = processor.map_or(0, |processor| processor.added_tokens(false));
+                let truncation = tokenizers::TruncationParams {
+                    max_length: truncation.max_length.saturating_sub(added_tokens),
+                    ..truncation.clone()
+                };
+                truncate_encodings(encoding, None, &truncation)?.0
+            }
+            None => encoding,
+        };
+
+        match processor {
+            Some(processor) => processor.process(encoding, None, true),
+            None => Ok(<dyn PostProcessor>::default_process(vec![encoding], true)?.remove(0)),
+        }
+    }
+
+    /// Encodes a batch with `encode_truncated_with`, then pads it with `padding`.
+    fn encode_batch_with(
+        &self,
+        inputs: &[String],
+        truncation: Option<&tokenizers::TruncationParams>,
+        padding: &tokenizers::PaddingParams,
+    ) -> Result<Vec<Encoding>, TokenizeError> {
+        let mut encodings = inputs
+            .par_iter()
+            .map(|input| self.encode_truncated_with(input, truncation))
+            .collect::<tokenizers::Result<Vec<_>>>()
+            .map_err(|_| TokenizeError::InputEncodingFailed)?;
+        pad_encodings(&mut encodings, padding).map_err(|_| TokenizeError::InputEncodingFailed)?;
+
+        Ok(encodings)
+    }
+}
+
 #[uniffi::export]
 impl CustomTokenizerInner {
-    /// Tokenizes an input string and returns a list of tokens.
+    /// Tokenizes an input string and returns a list of tokens, with byte offsets into
+    /// the input.
     fn tokenize(
         &self,
         input: &str,
         special_tokens: SpecialTokens,
+    ) -> Result<Vec<Token>, TokenizeError> {
+        let encoding = self.encode_single(input, special_tokens)?;
+
+        Ok(tokens_from_encoding(&encoding))
+    }
+
+    /// Tokenizes an input string like `tokenize`, with offsets counted in characters
+    /// (Unicode scalar values) of the original input instead of bytes.
+    ///
+    /// Offsets always refer to `input` as given, not to the text produced by the
+    /// tokenizer's normalizer, so they stay valid when it strips accents or lowercases.
+    fn tokenize_aligned(
+        &self,
+        input: &str,
+        special_tokens: SpecialTokens,
     ) -> Result<Vec<Token>, TokenizeError> {
         let encoding = self
             .tokenizer
-            .encode(input, special_tokens.into())
+            .encode_char_offsets(input, special_tokens.into())
             .map_err(|_| TokenizeError::InputEncodingFailed)?;
 
-        let tokens: Vec<Token> = encoding
-            .get_tokens()
-            .iter()
-            .cloned()
-            .zip(encoding.get_ids().iter().cloned())
-            .zip(encoding.get_offsets().iter().cloned())
-            .map(|((token, id), (start, end))| Token {
-                id,
-                token,
-                start: start as u32,
-                end: end as u32,
-            })
-            .collect();
+        Ok(tokens_from_encoding(&encoding))
+    }
 
-        Ok(tokens)
+    /// Tokenizes input that's already split into words, without splitting it again
+    /// on whitespace. Each token's `word_id` is the index of its word in `words`, and
+    /// its offsets are relative to that word.
+    fn tokenize_pretokenized(
+        &self,
+        words: Vec<String>,
+        special_tokens: SpecialTokens,
+    ) -> Result<Vec<Token>, TokenizeError> {
+        let encoding = self
+            .tokenizer
+            .encode(EncodeInput::Single(words.into()), special_tokens.into())
+            .map_err(|_| TokenizeError::InputEncodingFailed)?;
+
+        Ok(tokens_from_encoding(&encoding))
     }
 
     /// Tokenizes a list of input strings and returns a list of token IDs.
@@ -232,25 +631,275 @@ impl CustomTokenizerInner {
             .encode_batch(input, special_tokens.into())
             .map_err(|_| TokenizeError::InputEncodingFailed)?;
 
-        let token_ids: Vec<_> = encodings.par_iter().map(|e| e.get_ids().to_vec()).collect();
+        Ok(TokenizedBatch::from_encodings(&encodings))
+    }
 
-        let attention_mask: Vec<_> = encodings
-            .par_iter()
-            .map(|e| e.get_attention_mask().to_vec())
-            .collect();
+    /// Tokenizes a batch with special tokens into sequences of exactly `length` tokens,
+    /// padding and truncating them regardless of the configured `PaddingParams` and
+    /// `TruncationParams`, for models with a fixed input shape.
+    ///
+    /// The configured pad token and directions are kept.
+    fn encode_fixed(
+        &self,
+        inputs: Vec<String>,
+        length: u32,
+    ) -> Result<TokenizedBatch, TokenizeError> {
+        let padding = tokenizers::PaddingParams {
+            strategy: tokenizers::PaddingStrategy::Fixed(length as usize),
+            pad_to_multiple_of: None,
+            ..self.tokenizer.get_padding().cloned().unwrap_or_default()
+        };
+        let truncation = tokenizers::TruncationParams {
+            max_length: length as usize,
+            ..self.tokenizer.get_truncation().cloned().unwrap_or_default()
+        };
 
-        let type_ids: Vec<_> = encodings
+        let encodings = self.encode_batch_with(&inputs, Some(&truncation), &padding)?;
+
+        Ok(TokenizedBatch::from_encodings(&encodings))
+    }
+
+    /// Tokenizes a batch with special tokens, padded to its longest sequence whatever
+    /// the configured `PaddingParams`, into flat matrices for embedding inference.
+    /// The configured pad token, direction and multiple are kept, and `[PAD]` with
+    /// id 0 is used when padding isn't configured. Truncation is applied as configured.
+    fn encode_padded_matrix(&self, inputs: Vec<String>) -> Result<PaddedMatrix, TokenizeError> {
+        let padding = tokenizers::PaddingParams {
+            strategy: tokenizers::PaddingStrategy::BatchLongest,
+            ..self.tokenizer.get_padding().cloned().unwrap_or_default()
+        };
+        let encodings =
+            self.encode_batch_with(&inputs, self.tokenizer.get_truncation(), &padding)?;
+        let seq_len = encodings.first().map_or(0, Encoding::len);
+        let flatten = |field: fn(&Encoding) -> &[u32]| {
+            encodings
+                .iter()
+                .flat_map(|encoding| field(encoding).iter().copied())
+                .collect::<Vec<_>>()
+        };
+
+        Ok(PaddedMatrix {
+            input_ids: flatten(Encoding::get_ids),
+            attention_mask: flatten(Encoding::get_attention_mask),
+            type_ids: flatten(Encoding::get_type_ids),
+            seq_len: seq_len as u32,
+            batch_size: encodings.len() as u32,
+        })
+    }
+
+    /// Counts the tokens of each input without truncation or padding and summarizes
+    /// them, to choose `PaddingParams` and `TruncationParams` from real data. Only
+    /// the counts are kept, not the encodings. All stats are 0 for an empty batch.
+    fn token_length_stats(
+        &self,
+        inputs: Vec<String>,
+        special_tokens: SpecialTokens,
+    ) -> Result<LengthStats, TokenizeError> {
+        let add_special_tokens: bool = special_tokens.into();
+        let tokenizer = self.counting_tokenizer()?;
+
+        let mut lengths: Vec<u32> = inputs
             .par_iter()
-            .map(|e| e.get_type_ids().to_vec())
+            .map(|input| {
+                tokenizer
+                    .encode(input.as_str(), add_special_tokens)
+                    .map(|encoding| encoding.len() as u32)
+                    .map_err(|_| TokenizeError::InputEncodingFailed)
+            })
+            .collect::<Result<_, _>>()?;
+        lengths.sort_unstable();
+
+        let total: u64 = lengths.iter().map(|&len| len as u64).sum();
+        let percentile = |p: f64| {
+            let rank = (p * lengths.len() as f64).ceil() as usize;
+            lengths
+                .get(rank.saturating_sub(1))
+                .copied()
+                .unwrap_or_default()
+        };
+
+        Ok(LengthStats {
+            min: lengths.first().copied().unwrap_or_default(),
+            max: lengths.last().copied().unwrap_or_default(),
+            mean: if lengths.is_empty() {
+                0.0
+            } else {
+                total as f64 / lengths.len() as f64
+            },
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            total,
+        })
+    }
+
+    /// Tokenizes a list of sequence pairs and returns a list of token IDs.
+    /// `type_ids` tell the first sequence of each pair apart from the second.
+    fn tokenize_batch_pairs(
+        &self,
+        pairs: Vec<SequencePair>,
+        special_tokens: SpecialTokens,
+    ) -> Result<TokenizedBatch, TokenizeError> {
+        let input: Vec<EncodeInput> = pairs
+            .into_iter()
+            .map(|pair| EncodeInput::Dual(pair.first.into(), pair.second.into()))
             .collect();
 
-        Ok(TokenizedBatch {
-            token_ids,
-            attention_mask,
-            type_ids,
+        let encodings = self
+            .tokenizer
+            .encode_batch(input, special_tokens.into())
+            .map_err(|_| TokenizeError::InputEncodingFailed)?;
+
+        Ok(TokenizedBatch::from_encodings(&encodings))
+    }
+
+    /// Tokenizes an input string, returning its token IDs, strings and offsets along
+    /// with the attention mask that tells them apart from padding. Prefer this over
+    /// calling `get_ids` and `get_tokens`, which each encode the input.
+    fn encode(
+        &self,
+        input: &str,
+        special_tokens: SpecialTokens,
+    ) -> Result<TokenizedInput, TokenizeError> {
+        let encoding = self.encode_single(input, special_tokens)?;
+
+        Ok((&encoding).into())
+    }
+
+    /// Reports how many tokens of `input` the configured `TruncationParams` would drop,
+    /// so callers can re-chunk instead of losing content.
+    fn would_truncate(
+        &self,
+        input: &str,
+        special_tokens: SpecialTokens,
+    ) -> Result<TruncationInfo, TokenizeError> {
+        let add_special_tokens: bool = special_tokens.into();
+        let count = |encoding: &Encoding| encoding.get_attention_mask().iter().sum::<u32>();
+
+        let kept_tokens = self
+            .tokenizer
+            .encode(input, add_special_tokens)
+            .map(|e| count(&e))
+            .map_err(|_| TokenizeError::InputEncodingFailed)?;
+
+        if self.tokenizer.get_truncation().is_none() {
+            return Ok(TruncationInfo {
+                total_tokens: kept_tokens,
+                kept_tokens,
+                truncated_tokens: 0,
+            });
+        }
+
+        let total_tokens = self
+            .untruncated()?
+            .encode(input, add_special_tokens)
+            .map(|e| count(&e))
+            .map_err(|_| TokenizeError::InputEncodingFailed)?;
+
+        Ok(TruncationInfo {
+            total_tokens,
+            kept_tokens,
+            truncated_tokens: total_tokens.saturating_sub(kept_tokens),
         })
     }
 
+    /// Cuts `input` down to its first `max_tokens` tokens, decoded back to text with the
+    /// tokenizer's decoder. Special tokens aren't added or counted.
+    ///
+    /// Inputs that already fit are returned unchanged. The configured truncation and
+    /// padding are ignored.
+    fn truncate_to_tokens(&self, input: &str, max_tokens: u32) -> Result<String, TokenizeError> {
+        let tokenizer = self.counting_tokenizer()?;
+        let encoding = tokenizer
+            .encode(input, false)
+            .map_err(|_| TokenizeError::InputEncodingFailed)?;
+        let ids = encoding.get_ids();
+        if ids.len() <= max_tokens as usize {
+            return Ok(input.to_owned());
+        }
+
+        tokenizer
+            .decode(&ids[..max_tokens as usize], true)
+            .map_err(|_| TokenizeError::OutputDecodingFailed)
+    }
+
+    /// Splits `text` into chunks of at most `max_tokens` tokens, each starting with the
+    /// last `overlap_tokens` tokens of the previous one, decoded back to text.
+    ///
+    /// A chunk ends early at the last sentence end (`.`, `!` or `?`) in its second half,
+    /// if it has one, so chunks tend to hold whole sentences. Special tokens aren't added
+    /// or counted, and the configured truncation and padding are ignored.
+    fn chunk_by_tokens(
+        &self,
+        text: &str,
+        max_tokens: u32,
+        overlap_tokens: u32,
+    ) -> Result<Vec<String>, TokenizeError> {
+        if max_tokens == 0 || overlap_tokens >= max_tokens {
+            return Err(TokenizeError::InvalidChunkParams);
+        }
+        let (max_tokens, overlap_tokens) = (max_tokens as usize, overlap_tokens as usize);
+
+        let tokenizer = self.counting_tokenizer()?;
+        let encoding = tokenizer
+            .encode(text, false)
+            .map_err(|_| TokenizeError::InputEncodingFailed)?;
+        let ids = encoding.get_ids();
+        let offsets = encoding.get_offsets();
+
+        let ends_sentence = |i: usize| {
+            text.get(..offsets[i].1)
+                .and_then(|before| before.chars().next_back())
+                .is_some_and(|c| matches!(c, '.' | '!' | '?'))
+        };
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < ids.len() {
+            let mut end = (start + max_tokens).min(ids.len());
+            if end < ids.len() {
+                if let Some(i) = (start + max_tokens / 2..end)
+                    .rev()
+                    .find(|&i| ends_sentence(i))
+                {
+                    end = i + 1;
+                }
+            }
+
+            let chunk = tokenizer
+                .decode(&ids[start..end], true)
+                .map_err(|_| TokenizeError::OutputDecodingFailed)?;
+            chunks.push(chunk);
+
+            if end == ids.len() {
+                break;
+            }
+            start = end.saturating_sub(overlap_tokens).max(start + 1);
+        }
+
+        Ok(chunks)
+    }
+
+    /// Checks that decoding the tokens of `input` gives back `input`, to catch a
+    /// dictionary that doesn't match its model. Special tokens aren't added and are
+    /// skipped when decoding.
+    fn roundtrip_check(&self, input: &str) -> Result<RoundtripResult, TokenizeError> {
+        let tokenizer = self.counting_tokenizer()?;
+        let encoding = tokenizer
+            .encode(input, false)
+            .map_err(|_| TokenizeError::InputEncodingFailed)?;
+        let decoded = tokenizer
+            .decode(encoding.get_ids(), true)
+            .map_err(|_| TokenizeError::OutputDecodingFailed)?;
+
+        let lossless = decoded == input
+            || tokenizer.get_normalizer().is_some_and(|normalizer| {
+                let mut normalized = NormalizedString::from(input);
+                normalizer.normalize(&mut normalized).is_ok() && decoded == normalized.get()
+            });
+
+        Ok(RoundtripResult { lossless, decoded })
+    }
+
     /// Tokenizes an input string and return a list of token IDs.
     fn get_ids(
         &self,
@@ -258,9 +907,7 @@ impl CustomTokenizerInner {
         special_tokens: SpecialTokens,
     ) -> Result<Vec<u32>, TokenizeError> {
         Ok(self
-            .tokenizer
-            .encode(input, special_tokens.into())
-            .map_err(|_| TokenizeError::InputEncodingFailed)?
+            .encode_single(input, special_tokens)?
             .get_ids()
             .to_vec())
     }
@@ -271,13 +918,30 @@ impl CustomTokenizerInner {
         input: &str,
         special_tokens: SpecialTokens,
     ) -> Result<Vec<String>, TokenizeError> {
-        let encoding = self
-            .tokenizer
-            .encode(input, special_tokens.into())
-            .map_err(|_| TokenizeError::InputEncodingFailed)?;
+        let encoding = self.encode_single(input, special_tokens)?;
         Ok(encoding.get_tokens().to_vec())
     }
 
+    /// Tokenizes an input string and returns each token string with its ID, encoding
+    /// it once instead of calling both `get_tokens` and `get_ids`.
+    fn get_tokens_and_ids(
+        &self,
+        input: &str,
+        special_tokens: SpecialTokens,
+    ) -> Result<Vec<ReturnToken>, TokenizeError> {
+        let encoding = self.encode_single(input, special_tokens)?;
+
+        Ok(encoding
+            .get_tokens()
+            .iter()
+            .zip(encoding.get_ids())
+            .map(|(token, &id)| ReturnToken {
+                id,
+                token: token.clone(),
+            })
+            .collect())
+    }
+
     /// Gets the ID value of a given token.
     fn token_to_id(&self, token: &str) -> Option<u32> {
         self.tokenizer.token_to_id(token)
@@ -296,9 +960,618 @@ impl CustomTokenizerInner {
         })
     }
 
+    /// Gets the configured truncation length, the most tokens an encoding can have.
+    /// `None` when the tokenizer doesn't truncate, in which case callers have to know
+    /// the model's limit themselves. `model_max_length` lives in
+    /// `tokenizer_config.json`, not in the dictionary, so it isn't read.
+    fn max_length(&self) -> Option<u32> {
+        self.tokenizer
+            .get_truncation()
+            .and_then(|truncation| u32::try_from(truncation.max_length).ok())
+    }
+
     /// Decode a given list of token ids, back to a string
     fn decode(&self, tokens: Vec<u32>, special_tokens: SpecialTokens) -> Option<String> {
         let include_special_tokens: bool = special_tokens.into();
         self.tokenizer.decode(&tokens, !include_special_tokens).ok()
     }
+
+    /// Decodes a list of token IDs, returning each token with the byte range it covers
+    /// in the decoded string (the same string `decode` returns when skipping special tokens).
+    /// A token's range includes any separator the decoder puts in front of it.
+    ///
+    /// Special tokens don't appear in the decoded string, so they get an empty range
+    /// (`start == end`) at the position they would have been. So do tokens holding only
+    /// part of a character, like byte-level pieces of an emoji, whose text is covered by
+    /// the token completing it.
+    fn decode_with_offsets(&self, ids: Vec<u32>) -> Result<Vec<Token>, TokenizeError> {
+        let mut tokens = Vec::with_capacity(ids.len());
+        // The stream only decodes the last few tokens at each step, keeping this linear
+        let mut stream = self.tokenizer.decode_stream(true);
+        let mut start = 0;
+
+        for id in ids {
+            let token = self
+                .tokenizer
+                .id_to_token(id)
+                .ok_or(TokenizeError::OutputDecodingFailed)?;
+            let piece = stream
+                .step(id)
+                .map_err(|_| TokenizeError::OutputDecodingFailed)?;
+            let end = start + piece.map_or(0, |piece| piece.len());
+
+            tokens.push(Token {
+                id,
+                token,
+                start: start as u32,
+                end: end as u32,
+                word_id: None,
+            });
+            start = end;
+        }
+
+        Ok(tokens)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DICTIONARY: &str = r#"{
+        "version": "1.0",
+        "truncation": null,
+        "padding": null,
+        "added_tokens": [
+            {"id": 0, "content": "[PAD]", "single_word": false, "lstrip": false, "rstrip": false, "normalized": false, "special": true},
+            {"id": 1, "content": "[UNK]", "single_word": false, "lstrip": false, "rstrip": false, "normalized": false, "special": true},
+            {"id": 2, "content": "[CLS]", "single_word": false, "lstrip": false, "rstrip": false, "normalized": false, "special": true},
+            {"id": 3, "content": "[SEP]", "single_word": false, "lstrip": false, "rstrip": false, "normalized": false, "special": true}
+        ],
+        "normalizer": null,
+        "pre_tokenizer": {"type": "Whitespace"},
+        "post_processor": null,
+        "decoder": null,
+        "model": {
+            "type": "WordLevel",
+            "vocab": {"[PAD]": 0, "[UNK]": 1, "[CLS]": 2, "[SEP]": 3, "hello": 4, "world": 5, "how": 6, "are": 7, "you": 8},
+            "unk_token": "[UNK]"
+        }
+    }"#;
+
+    fn tokenizer(model_family: Option<ModelFamily>) -> CustomTokenizerInner {
+        CustomTokenizerInner::new(DICTIONARY, None, None, model_family, None).unwrap()
+    }
+
+    #[test]
+    fn model_family_adds_special_tokens() {
+        let ids = tokenizer(None)
+            .get_ids("hello world", SpecialTokens::Yes)
+            .unwrap();
+        assert_eq!(ids, [4, 5]);
+
+        let ids = tokenizer(Some(ModelFamily::Bert))
+            .get_ids("hello world", SpecialTokens::Yes)
+            .unwrap();
+        assert_eq!(ids, [2, 4, 5, 3]);
+
+        // The vocabulary has no `</s>`, so the default is skipped
+        let ids = tokenizer(Some(ModelFamily::Roberta))
+            .get_ids("hello world", SpecialTokens::Yes)
+            .unwrap();
+        assert_eq!(ids, [4, 5]);
+    }
+
+    #[test]
+    fn groups_tokens_by_word() {
+        let token = |id, token: &str, start, end, word_id| Token {
+            id,
+            token: token.to_owned(),
+            start,
+            end,
+            word_id,
+        };
+        let tokens = vec![
+            token(2, "[CLS]", 0, 0, None),
+            token(10, "token", 0, 5, Some(0)),
+            token(11, "##izer", 5, 9, Some(0)),
+            token(12, "works", 10, 15, Some(1)),
+            token(3, "[SEP]", 0, 0, None),
+        ];
+
+        let groups = group_by_word(tokens);
+        let groups: Vec<_> = groups
+            .iter()
+            .map(|g| {
+                (
+                    g.word_id,
+                    g.text.as_str(),
+                    g.start,
+                    g.end,
+                    g.token_ids.clone(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            groups,
+            [
+                (0, "tokenizer", 0, 9, vec![10, 11]),
+                (1, "works", 10, 15, vec![12]),
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_returns_padding_mask() {
+        let padding = PaddingParams {
+            strategy: PaddingStrategy::Fixed(16),
+            direction: PaddingDirection::Right,
+            pad_to_multiple_of: None,
+            pad_id: 0,
+            pad_type_id: 0,
+            pad_token: "[PAD]".to_owned(),
+        };
+        let tokenizer =
+            CustomTokenizerInner::new(DICTIONARY, Some(padding), None, None, None).unwrap();
+
+        let encoded = tokenizer
+            .encode("hello world how are you", SpecialTokens::No)
+            .unwrap();
+
+        assert_eq!(
+            encoded.token_ids,
+            [4, 5, 6, 7, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            encoded.attention_mask,
+            [1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn reports_truncation_loss() {
+        let truncation = TruncationParams {
+            direction: TruncationDirection::Right,
+            max_length: 4,
+            strategy: TruncationStrategy::LongestFirst,
+            stride: 0,
+        };
+        let tokenizer = CustomTokenizerInner::new(
+            DICTIONARY,
+            None,
+            Some(truncation),
+            Some(ModelFamily::Bert),
+            None,
+        )
+        .unwrap();
+
+        let info = tokenizer
+            .would_truncate("hello world how are you", SpecialTokens::Yes)
+            .unwrap();
+
+        assert_eq!(
+            (info.total_tokens, info.kept_tokens, info.truncated_tokens),
+            (7, 4, 3)
+        );
+    }
+
+    #[test]
+    fn aligned_offsets_use_original_chars() {
+        let dictionary = DICTIONARY
+            .replace(
+                r#""normalizer": null"#,
+                r#""normalizer": {"type": "BertNormalizer", "clean_text": true, "handle_chinese_chars": true, "strip_accents": true, "lowercase": true}"#,
+            )
+            .replace(r#""you": 8"#, r#""you": 8, "cafe": 9"#);
+        let tokenizer = CustomTokenizerInner::new(&dictionary, None, None, None, None).unwrap();
+        let input = "Déjà Café hello";
+
+        let offsets = |tokens: Vec<Token>| -> Vec<(String, u32, u32)> {
+            tokens
+                .into_iter()
+                .map(|t| (t.token, t.start, t.end))
+                .collect()
+        };
+        let bytes = offsets(tokenizer.tokenize(input, SpecialTokens::No).unwrap());
+        let chars = offsets(
+            tokenizer
+                .tokenize_aligned(input, SpecialTokens::No)
+                .unwrap(),
+        );
+
+        assert_eq!(bytes[1], ("cafe".to_owned(), 7, 12));
+        assert_eq!(chars[1], ("cafe".to_owned(), 5, 9));
+        let text: String = input.chars().skip(5).take(4).collect();
+        assert_eq!(text, "Café");
+    }
+
+    #[test]
+    fn truncates_to_token_budget() {
+        let dictionary = DICTIONARY.replace(
+            r#""decoder": null"#,
+            r###""decoder": {"type": "WordPiece", "prefix": "##", "cleanup": true}"###,
+        );
+        let truncation = TruncationParams {
+            direction: TruncationDirection::Right,
+            max_length: 2,
+            strategy: TruncationStrategy::LongestFirst,
+            stride: 0,
+        };
+        let tokenizer =
+            CustomTokenizerInner::new(&dictionary, None, Some(truncation), None, None).unwrap();
+
+        assert_eq!(
+            tokenizer
+                .truncate_to_tokens("hello world how are you", 3)
+                .unwrap(),
+            "hello world how"
+        );
+        assert_eq!(
+            tokenizer.truncate_to_tokens("hello  world", 5).unwrap(),
+            "hello  world"
+        );
+    }
+
+    #[test]
+    fn chunks_by_tokens() {
+        let dictionary = DICTIONARY
+            .replace(
+                r#""pre_tokenizer": {"type": "Whitespace"}"#,
+                r#""pre_tokenizer": {"type": "BertPreTokenizer"}"#,
+            )
+            .replace(r#""you": 8"#, r#""you": 8, ".": 9"#);
+        let tokenizer = CustomTokenizerInner::new(&dictionary, None, None, None, None).unwrap();
+
+        let chunks = tokenizer
+            .chunk_by_tokens("hello world. how are you you", 5, 1)
+            .unwrap();
+        assert_eq!(chunks, ["hello world .", ". how are you you"]);
+
+        let chunks = tokenizer.chunk_by_tokens("how are you", 2, 1).unwrap();
+        assert_eq!(chunks, ["how are", "are you"]);
+
+        assert!(matches!(
+            tokenizer.chunk_by_tokens("hello", 2, 2),
+            Err(TokenizeError::InvalidChunkParams)
+        ));
+    }
+
+    #[test]
+    fn batch_word_ids() {
+        let padding = PaddingParams {
+            strategy: PaddingStrategy::BatchLongest,
+            direction: PaddingDirection::Right,
+            pad_to_multiple_of: None,
+            pad_id: 0,
+            pad_type_id: 0,
+            pad_token: "[PAD]".to_owned(),
+        };
+        let tokenizer = CustomTokenizerInner::new(
+            DICTIONARY,
+            Some(padding),
+            None,
+            Some(ModelFamily::Bert),
+            None,
+        )
+        .unwrap();
+
+        let batch = tokenizer
+            .tokenize_batch(
+                vec!["hello world".to_owned(), "how".to_owned()],
+                SpecialTokens::Yes,
+            )
+            .unwrap();
+
+        assert_eq!(batch.word_ids[0], [None, Some(0), Some(1), None]);
+        assert_eq!(batch.word_ids[1], [None, Some(0), None, None]);
+        assert_eq!(batch.special_tokens_mask[0], [1, 0, 0, 1]);
+        assert_eq!(batch.special_tokens_mask[1], [1, 0, 1, 1]);
+    }
+
+    #[test]
+    fn checks_roundtrips() {
+        let dictionary = DICTIONARY.replace(
+            r#""normalizer": null"#,
+            r#""normalizer": {"type": "Lowercase"}"#,
+        );
+        let tokenizer = CustomTokenizerInner::new(&dictionary, None, None, None, None).unwrap();
+
+        let result = tokenizer.roundtrip_check("Hello world").unwrap();
+        assert!(result.lossless);
+        assert_eq!(result.decoded, "hello world");
+
+        let result = tokenizer.roundtrip_check("hello there").unwrap();
+        assert!(!result.lossless);
+        // `[UNK]` is a special token, so it is skipped
+        assert_eq!(result.decoded, "hello");
+    }
+
+    #[test]
+    fn tokenizes_pretokenized_words() {
+        let tokens = tokenizer(Some(ModelFamily::Bert))
+            .tokenize_pretokenized(
+                vec!["hello world".to_owned(), "how".to_owned()],
+                SpecialTokens::Yes,
+            )
+            .unwrap();
+        let tokens: Vec<_> = tokens
+            .iter()
+            .map(|t| (t.token.as_str(), t.start, t.end, t.word_id))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                ("[CLS]", 0, 0, None),
+                ("hello", 0, 5, Some(0)),
+                ("world", 6, 11, Some(0)),
+                ("how", 0, 3, Some(1)),
+                ("[SEP]", 0, 0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_fixed_length_batches() {
+        let padding = PaddingParams {
+            strategy: PaddingStrategy::BatchLongest,
+            direction: PaddingDirection::Right,
+            pad_to_multiple_of: Some(8),
+            pad_id: 0,
+            pad_type_id: 0,
+            pad_token: "[PAD]".to_owned(),
+        };
+        let tokenizer = CustomTokenizerInner::new(
+            DICTIONARY,
+            Some(padding),
+            None,
+            Some(ModelFamily::Bert),
+            None,
+        )
+        .unwrap();
+
+        let batch = tokenizer
+            .encode_fixed(
+                vec!["hello world how are you".to_owned(), "how".to_owned()],
+                5,
+            )
+            .unwrap();
+        assert_eq!(batch.token_ids, [vec![2, 4, 5, 6, 3], vec![2, 6, 3, 0, 0]]);
+        assert_eq!(batch.attention_mask[1], [1, 1, 1, 0, 0]);
+
+        // The tokenizer keeps its own configuration
+        let batch = tokenizer
+            .tokenize_batch(
+                vec!["hello world how are you".to_owned()],
+                SpecialTokens::Yes,
+            )
+            .unwrap();
+        assert_eq!(batch.token_ids[0].len(), 8);
+    }
+
+    #[test]
+    fn pairs_tokens_with_ids() {
+        let pairs = tokenizer(Some(ModelFamily::Bert))
+            .get_tokens_and_ids("hello world", SpecialTokens::Yes)
+            .unwrap();
+        let pairs: Vec<_> = pairs.iter().map(|p| (p.token.as_str(), p.id)).collect();
+
+        assert_eq!(
+            pairs,
+            [("[CLS]", 2), ("hello", 4), ("world", 5), ("[SEP]", 3)]
+        );
+    }
+
+    #[test]
+    fn encode_returns_every_field() {
+        let encoded = tokenizer(Some(ModelFamily::Bert))
+            .encode("hello world", SpecialTokens::Yes)
+            .unwrap();
+
+        assert_eq!(encoded.token_ids, [2, 4, 5, 3]);
+        assert_eq!(encoded.tokens, ["[CLS]", "hello", "world", "[SEP]"]);
+        assert_eq!(encoded.offsets[2], TokenOffset { start: 6, end: 11 });
+        assert_eq!(encoded.attention_mask, [1, 1, 1, 1]);
+        assert_eq!(encoded.type_ids, [0, 0, 0, 0]);
+        assert_eq!(encoded.special_tokens_mask, [1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn summarizes_token_lengths() {
+        let truncation = TruncationParams {
+            direction: TruncationDirection::Right,
+            max_length: 2,
+            strategy: TruncationStrategy::LongestFirst,
+            stride: 0,
+        };
+        let tokenizer =
+            CustomTokenizerInner::new(DICTIONARY, None, Some(truncation), None, None).unwrap();
+
+        let inputs = [
+            "hello",
+            "hello world",
+            "hello world how",
+            "hello world how are you",
+        ];
+        let stats = tokenizer
+            .token_length_stats(
+                inputs.iter().map(|&input| input.to_owned()).collect(),
+                SpecialTokens::No,
+            )
+            .unwrap();
+        assert_eq!(
+            stats,
+            LengthStats {
+                min: 1,
+                max: 5,
+                mean: 2.75,
+                p50: 2,
+                p95: 5,
+                total: 11,
+            }
+        );
+
+        let stats = tokenizer
+            .token_length_stats(vec![], SpecialTokens::No)
+            .unwrap();
+        assert_eq!(stats.total, 0);
+        assert_eq!(stats.mean, 0.0);
+    }
+
+    #[test]
+    fn input_normalization_keeps_original_offsets() {
+        let input = "ｈｅｌｌｏ world";
+        let ids = tokenizer(None).get_ids(input, SpecialTokens::No).unwrap();
+        assert_eq!(ids, [1, 5]);
+
+        let tokenizer =
+            CustomTokenizerInner::new(DICTIONARY, None, None, None, Some(NormalizationForm::Nfkc))
+                .unwrap();
+        let tokens = tokenizer.tokenize(input, SpecialTokens::No).unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.id, &input[token.start as usize..token.end as usize]))
+                .collect::<Vec<_>>(),
+            [(4, "ｈｅｌｌｏ"), (5, "world")]
+        );
+    }
+
+    #[test]
+    fn max_length_reads_truncation() {
+        assert_eq!(tokenizer(None).max_length(), None);
+
+        let truncation = TruncationParams {
+            direction: TruncationDirection::Right,
+            max_length: 512,
+            strategy: TruncationStrategy::LongestFirst,
+            stride: 0,
+        };
+        let tokenizer =
+            CustomTokenizerInner::new(DICTIONARY, None, Some(truncation), None, None).unwrap();
+        assert_eq!(tokenizer.max_length(), Some(512));
+    }
+
+    #[test]
+    fn encode_padded_matrix_pads_to_longest() {
+        let matrix = tokenizer(Some(ModelFamily::Bert))
+            .encode_padded_matrix(vec!["hello world".to_owned(), "how".to_owned()])
+            .unwrap();
+
+        assert_eq!(matrix.batch_size, 2);
+        assert_eq!(matrix.seq_len, 4);
+        assert_eq!(matrix.input_ids, [2, 4, 5, 3, 2, 6, 3, 0]);
+        assert_eq!(matrix.attention_mask, [1, 1, 1, 1, 1, 1, 1, 0]);
+        assert_eq!(matrix.type_ids.len(), 8);
+
+        let empty = tokenizer(None).encode_padded_matrix(vec![]).unwrap();
+        assert_eq!((empty.batch_size, empty.seq_len), (0, 0));
+        assert!(empty.input_ids.is_empty());
+
+        // Matches the tokenizer's own encoding with its configured truncation
+        let truncation = TruncationParams {
+            direction: TruncationDirection::Right,
+            max_length: 4,
+            strategy: TruncationStrategy::LongestFirst,
+            stride: 0,
+        };
+        let tokenizer = CustomTokenizerInner::new(
+            DICTIONARY,
+            None,
+            Some(truncation),
+            Some(ModelFamily::Bert),
+            None,
+        )
+        .unwrap();
+        let inputs = vec!["hello world how are you".to_owned(), "how".to_owned()];
+        let matrix = tokenizer.encode_padded_matrix(inputs.clone()).unwrap();
+        let batch = tokenizer
+            .tokenize_batch(inputs, SpecialTokens::Yes)
+            .unwrap();
+        assert_eq!(matrix.input_ids, [2, 4, 5, 3, 2, 6, 3, 0]);
+        assert_eq!(&matrix.input_ids[..4], batch.token_ids[0].as_slice());
+        assert_eq!(&matrix.input_ids[4..7], batch.token_ids[1].as_slice());
+    }
+
+    #[test]
+    fn decodes_with_offsets() {
+        let dictionary = DICTIONARY
+            .replace(
+                r#""decoder": null"#,
+                r###""decoder": {"type": "WordPiece", "prefix": "##", "cleanup": true}"###,
+            )
+            .replace(r#""you": 8}"#, r###""you": 8, "##s": 9}"###);
+        let tokenizer = CustomTokenizerInner::new(&dictionary, None, None, None, None).unwrap();
+
+        let ids = vec![2, 4, 9, 5, 3, 6, 7];
+        let decoded = tokenizer.decode(ids.clone(), SpecialTokens::No).unwrap();
+        assert_eq!(decoded, "hellos world how are");
+
+        let tokens = tokenizer.decode_with_offsets(ids).unwrap();
+        let ranges: Vec<_> = tokens
+            .iter()
+            .map(|t| (t.token.as_str(), t.start, t.end))
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                ("[CLS]", 0, 0),
+                ("hello", 0, 5),
+                ("##s", 5, 6),
+                ("world", 6, 12),
+                ("[SEP]", 12, 12),
+                ("how", 12, 16),
+                ("are", 16, 20),
+            ]
+        );
+        assert_eq!(&decoded[12..16], " how");
+    }
+
+    #[test]
+    fn tokenizes_batch_pairs() {
+        let padding = PaddingParams {
+            strategy: PaddingStrategy::BatchLongest,
+            direction: PaddingDirection::Right,
+            pad_to_multiple_of: None,
+            pad_id: 0,
+            pad_type_id: 0,
+            pad_token: "[PAD]".to_owned(),
+        };
+        let tokenizer = CustomTokenizerInner::new(
+            DICTIONARY,
+            Some(padding),
+            None,
+            Some(ModelFamily::Bert),
+            None,
+        )
+        .unwrap();
+        let pair = |first: &str, second: &str| SequencePair {
+            first: first.to_owned(),
+            second: second.to_owned(),
+        };
+
+        let batch = tokenizer
+            .tokenize_batch_pairs(
+                vec![
+                    pair("hello", "world how"),
+                    pair("hello world how are", "you"),
+                ],
+                SpecialTokens::Yes,
+            )
+            .unwrap();
+
+        assert_eq!(
+            batch.token_ids,
+            [vec![2, 4, 3, 5, 6, 3, 0, 0], vec![2, 4, 5, 6, 7, 3, 8, 3]]
+        );
+        assert_eq!(
+            batch.type_ids,
+            [vec![0, 0, 0, 1, 1, 1, 0, 0], vec![0, 0, 0, 0, 0, 0, 1, 1]]
+        );
+        assert_eq!(
+            batch.attention_mask,
+            [vec![1, 1, 1, 1, 1, 1, 0, 0], vec![1; 8]]
+        );
+    }
 }